@@ -0,0 +1,188 @@
+use colored::*;
+use futures::stream::{FuturesUnordered, StreamExt};
+use reqwest::StatusCode;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+
+use crate::sources::{is_html_response, Probe, ProbeFormat, SourceType};
+
+/// Result of probing a single source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeOutcome {
+    Ok,
+    AuthFailure,
+    QuotaExceeded,
+    SchemaChanged,
+    HtmlErrorPage,
+    Unreachable,
+    /// The source needs a key and none was loaded
+    NoKey,
+    /// The source has no probe, e.g. a WASM plugin
+    Skipped,
+}
+
+impl ProbeOutcome {
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, ProbeOutcome::Ok | ProbeOutcome::NoKey | ProbeOutcome::Skipped)
+    }
+}
+
+impl fmt::Display for ProbeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ProbeOutcome::Ok => "ok",
+            ProbeOutcome::AuthFailure => "auth failure",
+            ProbeOutcome::QuotaExceeded => "quota exceeded",
+            ProbeOutcome::SchemaChanged => "schema changed",
+            ProbeOutcome::HtmlErrorPage => "html error page",
+            ProbeOutcome::Unreachable => "unreachable",
+            ProbeOutcome::NoKey => "no key",
+            ProbeOutcome::Skipped => "skipped",
+        };
+        f.write_str(label)
+    }
+}
+
+pub struct ProbeReport {
+    pub source: String,
+    pub outcome: ProbeOutcome,
+    pub detail: String,
+    pub elapsed: Duration,
+}
+
+// Phrases upstreams put in otherwise successful responses when a quota is hit
+const QUOTA_MARKERS: [&str; 4] = [
+    "api count exceeded",
+    "quota exceeded",
+    "rate limit exceeded",
+    "too many requests",
+];
+
+/// Classifies a probe response from its status code and body
+pub(crate) fn classify(status: StatusCode, body: &str, format: &ProbeFormat) -> (ProbeOutcome, String) {
+    let lower = body.to_lowercase();
+
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return (ProbeOutcome::AuthFailure, format!("HTTP {}", status));
+    }
+    if status == StatusCode::TOO_MANY_REQUESTS || QUOTA_MARKERS.iter().any(|m| lower.contains(m)) {
+        return (ProbeOutcome::QuotaExceeded, format!("HTTP {}", status));
+    }
+
+    let html = is_html_response(body);
+    if !status.is_success() {
+        return if html {
+            (ProbeOutcome::HtmlErrorPage, format!("HTTP {}", status))
+        } else if status == StatusCode::NOT_FOUND || status == StatusCode::GONE {
+            (ProbeOutcome::SchemaChanged, format!("HTTP {}", status))
+        } else {
+            (ProbeOutcome::Unreachable, format!("HTTP {}", status))
+        };
+    }
+
+    match format {
+        ProbeFormat::Html => {
+            if html {
+                (ProbeOutcome::Ok, String::new())
+            } else {
+                (ProbeOutcome::SchemaChanged, "expected an HTML page".to_string())
+            }
+        }
+        ProbeFormat::Text => {
+            if html {
+                (ProbeOutcome::HtmlErrorPage, "expected plain text".to_string())
+            } else {
+                (ProbeOutcome::Ok, String::new())
+            }
+        }
+        ProbeFormat::Json | ProbeFormat::JsonArray | ProbeFormat::JsonField(_) => {
+            if html {
+                return (ProbeOutcome::HtmlErrorPage, "expected JSON".to_string());
+            }
+            let json: serde_json::Value = match serde_json::from_str(body) {
+                Ok(json) => json,
+                Err(e) => return (ProbeOutcome::SchemaChanged, format!("invalid JSON: {}", e)),
+            };
+            match format {
+                ProbeFormat::JsonArray if !json.is_array() => {
+                    (ProbeOutcome::SchemaChanged, "expected a JSON array".to_string())
+                }
+                ProbeFormat::JsonField(field) if json.get(field).is_none() => {
+                    (ProbeOutcome::SchemaChanged, format!("missing field '{}'", field))
+                }
+                _ => (ProbeOutcome::Ok, String::new()),
+            }
+        }
+    }
+}
+
+async fn probe_source(source: &SourceType, domain: &str, timeout_duration: Duration) -> ProbeReport {
+    let start = Instant::now();
+    let report = |outcome, detail: String| ProbeReport {
+        source: source.name(),
+        outcome,
+        detail,
+        elapsed: start.elapsed(),
+    };
+
+    let Some(Probe { request, format }) = source.probe(domain) else {
+        if !source.requires_key() || source.has_key() {
+            return report(ProbeOutcome::Skipped, String::new());
+        }
+        return report(ProbeOutcome::NoKey, String::new());
+    };
+
+    let response = match timeout(timeout_duration, request.send()).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return report(ProbeOutcome::Unreachable, e.to_string()),
+        Err(_) => return report(ProbeOutcome::Unreachable, "timed out".to_string()),
+    };
+
+    let status = response.status();
+    let body = match timeout(timeout_duration, response.text()).await {
+        Ok(Ok(body)) => body,
+        Ok(Err(e)) => return report(ProbeOutcome::Unreachable, e.to_string()),
+        Err(_) => return report(ProbeOutcome::Unreachable, "timed out reading body".to_string()),
+    };
+
+    let (outcome, detail) = classify(status, &body, &format);
+    report(outcome, detail)
+}
+
+/// Probes every source concurrently and returns one report per source,
+/// sorted by source name
+pub async fn check_sources(sources: &[SourceType], domain: &str, timeout_duration: Duration) -> Vec<ProbeReport> {
+    let mut tasks: FuturesUnordered<_> = sources
+        .iter()
+        .map(|source| probe_source(source, domain, timeout_duration))
+        .collect();
+
+    let mut reports = Vec::with_capacity(sources.len());
+    while let Some(report) = tasks.next().await {
+        reports.push(report);
+    }
+    reports.sort_by(|a, b| a.source.cmp(&b.source));
+    reports
+}
+
+/// Prints the reports as a table and returns the number of failing sources
+pub fn print_reports(reports: &[ProbeReport]) -> usize {
+    for report in reports {
+        let outcome = format!("{:<16}", report.outcome.to_string());
+        let outcome = match report.outcome {
+            ProbeOutcome::Ok => outcome.green(),
+            ProbeOutcome::NoKey | ProbeOutcome::Skipped => outcome.dimmed(),
+            _ => outcome.red(),
+        };
+        println!(
+            "  {:<14} {} {:>8}ms  {}",
+            report.source,
+            outcome,
+            report.elapsed.as_millis(),
+            report.detail
+        );
+    }
+
+    reports.iter().filter(|r| r.outcome.is_failure()).count()
+}
@@ -1,11 +1,13 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use colored::*;
 use log::{error, info};
 use serde_json::Value;
 use std::time::{Duration, Instant};
 
+mod check;
 mod runner;
+mod settings;
 mod sources;
 
 const BANNER: &str = r#"
@@ -18,18 +20,21 @@ const BANNER: &str = r#"
     "#;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Domain to find subdomains for
-    #[arg(required = true)]
-    domain: String,
+    #[arg(required_unless_present = "list_sources")]
+    domain: Option<String>,
 
     /// Number of concurrent threads
     #[arg(short = 'n', long, default_value = "10")]
     threads: usize,
 
     /// Timeout in seconds for requests
-    #[arg(short = 't', long, default_value = "30")]
+    #[arg(short = 't', long, default_value = "30", global = true)]
     timeout: u64,
 
     /// Maximum enumeration time in minutes
@@ -37,16 +42,93 @@ struct Args {
     max_time: u64,
 
     /// Verbose output
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
 
     /// API keys file path
-    #[arg(short = 'k', long)]
+    #[arg(short = 'k', long, global = true)]
     keys_file: Option<String>,
 
+    /// Config file path (TOML) with per-source timeouts and priorities
+    #[arg(short = 'c', long, global = true)]
+    config: Option<String>,
+
     /// Proxy URL (e.g., http://proxy.infiniteproxies.com:1111)
     #[arg(short = 'p', long)]
     proxy: Option<String>,
+
+    /// Sources to use, comma separated (e.g., crtsh,virustotal)
+    #[arg(short = 's', long, value_delimiter = ',', global = true)]
+    sources: Vec<String>,
+
+    /// Sources to skip, comma separated
+    #[arg(short = 'e', long, value_delimiter = ',', global = true)]
+    exclude_sources: Vec<String>,
+
+    /// Use all sources, including slow and unreliable ones
+    #[arg(long)]
+    all: bool,
+
+    /// Use only default sources that answer with a single request
+    #[arg(long, visible_alias = "passive-fast", conflicts_with = "all")]
+    fast: bool,
+
+    /// Also write every archived URL found by the webarchive source to this file
+    #[arg(long)]
+    archive_urls: Option<String>,
+
+    /// Only keep DNSDB names first seen after this time (YYYY-MM-DD, unix seconds or an age like 90d)
+    #[arg(long, value_parser = parse_seen_time)]
+    seen_after: Option<i64>,
+
+    /// Only keep DNSDB names last seen before this time (same formats as --seen-after)
+    #[arg(long, value_parser = parse_seen_time)]
+    seen_before: Option<i64>,
+
+    /// Print subdomains and a per-source report as JSON
+    #[arg(short = 'j', long)]
+    json: bool,
+
+    /// Exit with a non-zero code when any source fails
+    #[arg(long)]
+    fail_on_error: bool,
+
+    /// List available sources and exit
+    #[arg(short = 'l', long)]
+    list_sources: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Probe every source against a known domain and report broken ones
+    Check {
+        /// Domain to probe the sources with
+        #[arg(short, long, default_value = "hackerone.com")]
+        domain: String,
+    },
+}
+
+/// Parses a time fence into what DNSDB expects: absolute unix seconds, or
+/// negative seconds relative to now for ages such as "90d" or "12h"
+fn parse_seen_time(value: &str) -> Result<i64, String> {
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).expect("Midnight is a valid time").and_utc().timestamp());
+    }
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Ok(seconds);
+    }
+
+    let unit = match value.chars().last() {
+        Some('d') => 86400,
+        Some('h') => 3600,
+        Some('m') => 60,
+        Some('s') => 1,
+        _ => return Err(format!("expected YYYY-MM-DD, unix seconds or an age like 90d, got '{}'", value)),
+    };
+    value[..value.len() - 1]
+        .parse::<i64>()
+        .map(|amount| -(amount * unit))
+        .map_err(|_| format!("expected YYYY-MM-DD, unix seconds or an age like 90d, got '{}'", value))
 }
 
 fn load_api_keys(path: &str) -> Result<Value> {
@@ -56,6 +138,31 @@ fn load_api_keys(path: &str) -> Result<Value> {
         .map_err(|e| anyhow::anyhow!("Failed to parse keys file: {}", e))
 }
 
+fn list_sources(api_keys: Option<&Value>, settings: &settings::Settings) {
+    let mut sources = sources::SourceProvider::get_configured(api_keys, settings);
+    sources.sort_by_key(|source| source.name());
+
+    let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+
+    println!("Available sources [{}]:", sources.len());
+    println!("  NAME           NEEDS KEY  KEY LOADED   RECURSIVE  DEFAULT");
+    for source in &sources {
+        let key_loaded = if source.requires_key() || source.has_key() {
+            yes_no(source.has_key())
+        } else {
+            "-"
+        };
+        println!(
+            "  {:<14} {:<10} {:<12} {:<10} {}",
+            source.name(),
+            yes_no(source.requires_key()),
+            key_loaded,
+            yes_no(source.supports_recursive()),
+            yes_no(source.is_default())
+        );
+    }
+}
+
 fn format_duration(duration: Duration) -> String {
     let total_secs = duration.as_secs();
     let hours = total_secs / 3600;
@@ -78,6 +185,7 @@ fn format_duration(duration: Duration) -> String {
 async fn main() -> Result<()> {
     let args = Args::parse();
     let start_time = Instant::now();
+    let domain = args.domain.clone().unwrap_or_default();
 
     // Initialize logging with appropriate filters
     if args.verbose {
@@ -109,7 +217,7 @@ async fn main() -> Result<()> {
         println!("{}", "[ Subdomain Enumeration Tool ]".bright_blue());
         println!();
         
-        info!("Starting subdomain enumeration for: {}", args.domain);
+        info!("Starting subdomain enumeration for: {}", domain);
     }
 
     // Load API keys if provided
@@ -130,6 +238,51 @@ async fn main() -> Result<()> {
         None
     };
 
+    // Load the config file if provided; a broken one is fatal since it
+    // changes how the run behaves
+    let mut settings = match args.config.as_ref() {
+        Some(path) => settings::Settings::load(path)?,
+        None => settings::Settings::default(),
+    };
+    if let Some(path) = args.archive_urls.as_ref() {
+        settings.webarchive.urls_output = Some(path.into());
+    }
+    if args.seen_after.is_some() {
+        settings.dnsdb.seen_after = args.seen_after;
+    }
+    if args.seen_before.is_some() {
+        settings.dnsdb.seen_before = args.seen_before;
+    }
+
+    if args.list_sources {
+        list_sources(api_keys.as_ref(), &settings);
+        return Ok(());
+    }
+
+    if let Some(Command::Check { domain }) = &args.command {
+        let sources = sources::SourceProvider::get_configured(api_keys.as_ref(), &settings);
+        // Check every source unless told otherwise
+        let selection = sources::SourceSelection {
+            include: args.sources.clone(),
+            exclude: args.exclude_sources.clone(),
+            all: true,
+            fast: false,
+        };
+        let mut sources = sources::SourceProvider::select(sources, &selection)?;
+        for source in &mut sources {
+            source.apply_settings(&settings);
+        }
+
+        println!("Checking {} sources against {}:", sources.len(), domain);
+        let reports = check::check_sources(&sources, domain, Duration::from_secs(args.timeout)).await;
+        let failures = check::print_reports(&reports);
+        if failures > 0 {
+            eprintln!("{} {} source(s) failing", "[!]".red(), failures);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Format proxy URL with credentials if provided
     let proxy = args.proxy.map(|p| {
         if p.contains("@") {
@@ -150,43 +303,63 @@ async fn main() -> Result<()> {
         verbose: args.verbose,
         api_keys: api_keys.clone(),
         proxy: proxy.clone(),
+        source_selection: sources::SourceSelection {
+            include: args.sources,
+            exclude: args.exclude_sources,
+            all: args.all,
+            fast: args.fast,
+        },
+        settings,
     };
 
-    let runner = runner::Runner::new(config);
+    let runner = runner::Runner::new(config)?;
 
-    match runner.enumerate_domain(&args.domain).await {
-        Ok(subdomains) => {
-            if args.verbose {
-                println!("\n{}", "[+] Found Subdomains:".green());
-                println!("{}", "-".repeat(50).dimmed());
-            }
-            
-            let mut sorted: Vec<_> = subdomains.into_iter().collect();
-            sorted.sort();
+    match runner.enumerate_domain(&domain).await {
+        Ok(enumeration) => {
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&enumeration)?);
+            } else {
+                if args.verbose {
+                    println!("\n{}", "[+] Found Subdomains:".green());
+                    println!("{}", "-".repeat(50).dimmed());
+                }
+
+                let sorted = &enumeration.subdomains;
             
-            // Print each subdomain with proper indentation
-            if !sorted.is_empty() {
-                for subdomain in &sorted {
-                    if args.verbose {
-                        println!("  {}", subdomain.yellow());
-                    } else {
-                        println!("{}", subdomain);
+                // Print each subdomain with proper indentation
+                if !sorted.is_empty() {
+                    for subdomain in sorted {
+                        if args.verbose {
+                            println!("  {}", subdomain.yellow());
+                        } else {
+                            println!("{}", subdomain);
+                        }
                     }
-                }
 
-                if args.verbose {
+                    if args.verbose {
+                        println!();
+                        println!("{}", "-".repeat(50).dimmed());
+                        println!("Total unique subdomains found: {}", sorted.len());
+                        println!("Total enumeration time: {}", format_duration(start_time.elapsed()));
+                        println!("{}", "-".repeat(50).dimmed());
+                    }
+                } else if args.verbose {
+                    println!("  No subdomains found");
                     println!();
                     println!("{}", "-".repeat(50).dimmed());
-                    println!("Total unique subdomains found: {}", sorted.len());
                     println!("Total enumeration time: {}", format_duration(start_time.elapsed()));
                     println!("{}", "-".repeat(50).dimmed());
                 }
-            } else if args.verbose {
-                println!("  No subdomains found");
-                println!();
-                println!("{}", "-".repeat(50).dimmed());
-                println!("Total enumeration time: {}", format_duration(start_time.elapsed()));
-                println!("{}", "-".repeat(50).dimmed());
+            }
+
+            let failed: Vec<_> = enumeration.failed_sources().collect();
+            if args.fail_on_error && !failed.is_empty() {
+                for report in &failed {
+                    if let Some(e) = &report.error {
+                        eprintln!("{} {}: {}", "[!]".red(), report.source, e);
+                    }
+                }
+                std::process::exit(1);
             }
             Ok(())
         }
@@ -1,15 +1,19 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use futures::stream::{FuturesUnordered, StreamExt};
-use log::{debug, info, warn};
+use log::{info, warn};
+use serde::Serialize;
 use serde_json::Value;
-use std::collections::{HashSet, HashMap};
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Semaphore;
 use tokio::time::{timeout, Instant};
 
-use crate::sources::{SourceProvider, SourceType};
+use crate::settings::Settings;
+use crate::sources::{
+    PagePool, SourceError, SourceProvider, SourceSelection, SourceType, DEFAULT_PAGE_CONCURRENCY,
+};
 
 pub struct Config {
     pub threads: usize,
@@ -18,6 +22,35 @@ pub struct Config {
     pub verbose: bool,
     pub api_keys: Option<Value>,
     pub proxy: Option<String>,
+    pub source_selection: SourceSelection,
+    pub settings: Settings,
+}
+
+/// How a single source fared during a run
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceReport {
+    pub source: String,
+    pub results: usize,
+    pub elapsed_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SourceError>,
+}
+
+/// Everything a run produced
+#[derive(Debug, Serialize)]
+pub struct Enumeration {
+    pub domain: String,
+    pub subdomains: Vec<String>,
+    pub sources: Vec<SourceReport>,
+}
+
+impl Enumeration {
+    /// Sources that failed for a reason other than missing configuration
+    pub fn failed_sources(&self) -> impl Iterator<Item = &SourceReport> {
+        self.sources
+            .iter()
+            .filter(|report| report.error.as_ref().is_some_and(SourceError::is_failure))
+    }
 }
 
 pub struct Runner {
@@ -27,25 +60,54 @@ pub struct Runner {
 }
 
 impl Runner {
-    pub fn new(config: Config) -> Self {
+    pub fn new(config: Config) -> Result<Self> {
         // Override the default client with proxy if configured
         if let Some(ref proxy) = config.proxy {
             crate::sources::create_client_with_proxy(Some(proxy.clone()));
         }
 
-        let sources = if let Some(ref keys) = config.api_keys {
-            SourceProvider::get_sources_with_keys(keys)
-        } else {
-            SourceProvider::get_sources()
-        };
+        let sources = SourceProvider::get_configured(config.api_keys.as_ref(), &config.settings);
+        let mut sources = SourceProvider::select(sources, &config.source_selection)?;
+        for source in &mut sources {
+            source.apply_settings(&config.settings);
+        }
+
+        // Tasks queue on the semaphore in the order they are pushed, so
+        // sorting here decides which sources start first
+        sources.sort_by_key(|source| std::cmp::Reverse(Self::source_priority(&config, source)));
 
-        Runner { 
-            config, 
+        Ok(Runner {
+            config,
             sources,
             active_tasks: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    fn source_priority(config: &Config, source: &SourceType) -> i32 {
+        config
+            .settings
+            .source(&source.name())
+            .and_then(|settings| settings.priority)
+            .unwrap_or_else(|| source.default_priority())
+    }
+
+    fn source_timeout(&self, source: &SourceType) -> Duration {
+        match self.config.settings.source(&source.name()).and_then(|settings| settings.timeout) {
+            Some(secs) => Duration::from_secs(secs),
+            None => source
+                .default_timeout()
+                .map_or(self.config.timeout, |budget| budget.max(self.config.timeout)),
         }
     }
 
+    fn source_concurrency(&self, source: &SourceType) -> usize {
+        self.config
+            .settings
+            .source(&source.name())
+            .and_then(|settings| settings.concurrency)
+            .unwrap_or(DEFAULT_PAGE_CONCURRENCY)
+    }
+
     async fn test_proxy(&self) -> Result<()> {
         if let Some(proxy_url) = &self.config.proxy {
             if self.config.verbose {
@@ -68,11 +130,10 @@ impl Runner {
         Ok(())
     }
 
-    pub async fn enumerate_domain(&self, domain: &str) -> Result<HashSet<String>> {
+    pub async fn enumerate_domain(&self, domain: &str) -> Result<Enumeration> {
         let enumeration_start = Instant::now();
         let mut all_subdomains = HashSet::new();
-        let mut source_map: HashMap<String, HashSet<String>> = HashMap::new();
-        let mut source_timings: HashMap<String, Duration> = HashMap::new();
+        let mut reports: Vec<SourceReport> = Vec::new();
         
         // Test proxy before starting enumeration
         self.test_proxy().await?;
@@ -88,46 +149,29 @@ impl Runner {
         // Initialize tasks for each source
         for source in &self.sources {
             let domain = domain.to_string();
-            let timeout_duration = self.config.timeout;
-            let source = (*source).clone();
+            let timeout_duration = self.source_timeout(source);
+            let mut source = (*source).clone();
+            source.set_timeout(timeout_duration);
+            source.set_page_pool(PagePool::new(
+                Some(semaphore.clone()),
+                self.source_concurrency(&source),
+            ));
             let sem = semaphore.clone();
             let active_tasks = self.active_tasks.clone();
-            let verbose = self.config.verbose;
             
             let task = async move {
-                // Acquire semaphore permit
-                let _permit = sem.acquire().await.context("Failed to acquire semaphore")?;
+                // The semaphore is never closed, so acquiring cannot fail
+                let _permit = sem.acquire().await.expect("Semaphore closed");
                 active_tasks.fetch_add(1, Ordering::SeqCst);
 
-                let source_name = source.name();
                 let source_start = Instant::now();
-                let result = async {
-                    let source_future = source.enumerate(&domain);
-                    match timeout(timeout_duration, source_future).await {
-                        Ok(result) => {
-                            match result {
-                                Ok(domains) => {
-                                    Ok((domains, source_name.clone(), source_start.elapsed()))
-                                }
-                                Err(e) => {
-                                    if verbose {
-                                        warn!("Source error: {}", e);
-                                    }
-                                    Err(e)
-                                }
-                            }
-                        }
-                        Err(_) => {
-                            if verbose {
-                                warn!("Source timed out");
-                            }
-                            Ok((HashSet::new(), source_name.clone(), timeout_duration))
-                        }
-                    }
-                }.await;
+                let result = match timeout(timeout_duration, source.enumerate(&domain)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(SourceError::Timeout),
+                };
 
                 active_tasks.fetch_sub(1, Ordering::SeqCst);
-                result
+                (source.name(), result, source_start.elapsed())
             };
 
             tasks.push(task);
@@ -145,19 +189,12 @@ impl Runner {
             tokio::select! {
                 result = tasks.next() => {
                     match result {
-                        Some(result) => {
+                        Some((source_name, result, elapsed)) => {
                             completed_sources += 1;
                             match result {
-                                Ok((domains, source_name, elapsed)) => {
+                                Ok(domains) => {
                                     let new_domains = domains.len();
-                                    source_timings.insert(source_name.clone(), elapsed);
-                                    for subdomain in domains {
-                                        // Track sources for each subdomain
-                                        source_map.entry(subdomain.clone())
-                                            .or_default()
-                                            .insert(source_name.clone());
-                                        all_subdomains.insert(subdomain);
-                                    }
+                                    all_subdomains.extend(domains);
                                     if self.config.verbose && new_domains > 0 {
                                         info!(
                                             "[+] Source {}/{} completed | {} subdomains found in {:?}", 
@@ -167,15 +204,23 @@ impl Runner {
                                             elapsed
                                         );
                                     }
+                                    reports.push(SourceReport {
+                                        source: source_name,
+                                        results: new_domains,
+                                        elapsed_ms: elapsed.as_millis(),
+                                        error: None,
+                                    });
                                 }
                                 Err(e) => {
-                                    // Only log critical errors
-                                    if self.config.verbose && 
-                                       !e.to_string().contains("API_KEY") && 
-                                       !e.to_string().contains("404") &&
-                                       !e.to_string().contains("timeout") {
-                                        warn!("Source error: {}", e);
+                                    if self.config.verbose && e.is_failure() {
+                                        warn!("Source {} failed: {}", source_name, e);
                                     }
+                                    reports.push(SourceReport {
+                                        source: source_name,
+                                        results: 0,
+                                        elapsed_ms: elapsed.as_millis(),
+                                        error: Some(e),
+                                    });
                                 }
                             }
                         }
@@ -195,6 +240,20 @@ impl Runner {
             }
         }
 
+        // Sources still running when the overall budget ran out
+        for source in &self.sources {
+            let name = source.name();
+            if !reports.iter().any(|report| report.source == name) {
+                reports.push(SourceReport {
+                    source: name,
+                    results: 0,
+                    elapsed_ms: self.config.max_enumeration_time.as_millis(),
+                    error: Some(SourceError::Timeout),
+                });
+            }
+        }
+        reports.sort_by(|a, b| b.results.cmp(&a.results).then_with(|| a.source.cmp(&b.source)));
+
         // Filter and sort subdomains
         let mut filtered: Vec<_> = all_subdomains
             .into_iter()
@@ -207,35 +266,19 @@ impl Runner {
         info!("[+] Enumeration completed in {:?}", elapsed);
         if self.config.verbose {
             info!("[+] Source statistics:");
-            let mut source_stats: Vec<_> = source_map.iter()
-                .map(|(_, sources)| {
-                    sources.iter()
-                        .map(|source| (source.clone(), 1))
-                        .collect::<Vec<_>>()
-                })
-                .flatten()
-                .fold(HashMap::new(), |mut acc, (source, count)| {
-                    *acc.entry(source).or_insert(0) += count;
-                    acc
-                })
-                .into_iter()
-                .map(|(source, count)| {
-                    let timing = source_timings.get(&source)
-                        .cloned()
-                        .unwrap_or_else(|| Duration::from_secs(0));
-                    (source, count, timing)
-                })
-                .collect();
-
-            source_stats.sort_by(|a, b| b.1.cmp(&a.1));  // Sort by count descending
-            for (source, count, timing) in source_stats {
-                info!(
-                    "    - {}: {} results in {:?}",
-                    source, count, timing
-                );
+            for report in &reports {
+                let timing = Duration::from_millis(report.elapsed_ms as u64);
+                match &report.error {
+                    Some(e) => info!("    - {}: {} ({:?})", report.source, e, timing),
+                    None => info!("    - {}: {} results in {:?}", report.source, report.results, timing),
+                }
             }
         }
 
-        Ok(filtered.into_iter().collect())
+        Ok(Enumeration {
+            domain: domain.to_string(),
+            subdomains: filtered,
+            sources: reports,
+        })
     }
 }
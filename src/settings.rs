@@ -0,0 +1,126 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::sources::{CensysSettings, CommonCrawlSettings, CrtShSettings, DNSDBSettings, DeclarativeSettings, GitHubSettings, GitLabSettings, LocalSettings, NetlasSettings, PluginSettings, SearchEngineSettings, SecurityTrailsSettings, ShodanSettings, WebArchiveSettings};
+
+/// Options read from the `--config` TOML file
+///
+/// ```toml
+/// [sources.commoncrawl]
+/// timeout = 300
+/// priority = -10
+/// concurrency = 6
+///
+/// [commoncrawl]
+/// indexes = "last"
+/// count = 24
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Per-source overrides, keyed by source name
+    pub sources: HashMap<String, SourceSettings>,
+    pub commoncrawl: CommonCrawlSettings,
+    pub webarchive: WebArchiveSettings,
+    pub crtsh: CrtShSettings,
+    pub github: GitHubSettings,
+    pub gitlab: GitLabSettings,
+    pub censys: CensysSettings,
+    pub dnsdb: DNSDBSettings,
+    pub shodan: ShodanSettings,
+    pub netlas: NetlasSettings,
+    pub securitytrails: SecurityTrailsSettings,
+    pub search_engines: SearchEngineSettings,
+    pub local: LocalSettings,
+    /// Sources defined entirely in the config file
+    pub declarative: Vec<DeclarativeSettings>,
+    /// Sources compiled to WebAssembly
+    pub plugins: Vec<PluginSettings>,
+}
+
+/// Overrides for a single source
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourceSettings {
+    /// Timeout in seconds, replacing the source's default budget
+    pub timeout: Option<u64>,
+    /// Scheduling priority; higher values are started first
+    pub priority: Option<i32>,
+    /// Page requests the source may run at once
+    pub concurrency: Option<usize>,
+}
+
+impl Settings {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config file: {}", e))?;
+        let settings: Self = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file: {}", e))?;
+        for definition in &settings.declarative {
+            definition
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Invalid declarative source: {}", e))?;
+        }
+        settings
+            .local
+            .validate()
+            .map_err(|e| anyhow::anyhow!("Invalid local dataset: {}", e))?;
+        for plugin in &settings.plugins {
+            plugin
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Invalid plugin: {}", e))?;
+        }
+        let mut names = HashSet::new();
+        let configured = settings.declarative.iter().map(|d| &d.name).chain(settings.plugins.iter().map(|p| &p.name));
+        for name in configured {
+            if !names.insert(name.to_lowercase()) {
+                anyhow::bail!("Configured source '{}' is defined more than once", name);
+            }
+        }
+        Ok(settings)
+    }
+
+    pub fn source(&self, name: &str) -> Option<&SourceSettings> {
+        self.sources.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(name: &str, content: &str) -> Result<Settings> {
+        let path = std::env::temp_dir().join(format!("subfinder-settings-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let settings = Settings::load(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        settings
+    }
+
+    #[test]
+    fn configured_sources_cannot_take_built_in_names() {
+        let declarative = "[[declarative]]\nname = \"CrtSh\"\nurl = \"https://example.com/?q={domain}\"\n";
+        let error = load_str("declarative", declarative).expect_err("clash accepted");
+        assert!(error.to_string().contains("built-in"), "{}", error);
+
+        let plugin = "[[plugins]]\nname = \"shodan\"\npath = \"shodan.wasm\"\n";
+        let error = load_str("plugin", plugin).expect_err("clash accepted");
+        assert!(error.to_string().contains("built-in"), "{}", error);
+    }
+
+    #[test]
+    fn configured_sources_need_distinct_names() {
+        let plugin = std::env::temp_dir().join(format!("subfinder-settings-{}.wat", std::process::id()));
+        std::fs::write(&plugin, "(module)").unwrap();
+        let content = format!(
+            "[[declarative]]\nname = \"inventory\"\nurl = \"https://example.com/?q={{domain}}\"\n\n\
+             [[plugins]]\nname = \"Inventory\"\npath = {:?}\n",
+            plugin
+        );
+        let result = load_str("duplicate", &content);
+        let _ = std::fs::remove_file(&plugin);
+        let error = result.expect_err("duplicate accepted");
+        assert!(error.to_string().contains("more than once"), "{}", error);
+    }
+}
@@ -1,16 +1,23 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
+use url::Url;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, is_valid_subdomain, parse_json, partial_result, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://otx.alienvault.com/api/v1/indicators/domain";
+const URL_LIST_LIMIT: usize = 500;
+// Popular domains have tens of thousands of URLs; their hosts repeat long
+// before the list ends
+const MAX_URL_LIST_PAGES: usize = 20;
 
 #[derive(Clone)]
 pub struct AlienVaultSource {
     client: Arc<Client>,
+    api_keys: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,6 +30,21 @@ struct PassiveDNS {
     hostname: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct UrlListResponse {
+    url_list: Vec<UrlEntry>,
+    #[serde(default)]
+    has_next: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlEntry {
+    #[serde(default)]
+    hostname: String,
+    #[serde(default)]
+    url: String,
+}
+
 impl AlienVaultSource {
     pub fn new() -> Self {
         Self {
@@ -30,67 +52,109 @@ impl AlienVaultSource {
                 .user_agent("subfinder-rs")
                 .build()
                 .expect("Failed to build HTTP client")),
+            api_keys: Vec::new(),
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
-        let start_time = Instant::now();
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    // The API works without a key; a key only raises the rate limits
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url);
+        match self.api_keys.first() {
+            Some(api_key) => request.header("X-OTX-API-KEY", api_key),
+            None => request,
+        }
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.get(&format!("{}/{}/passive_dns", API_URL, domain)),
+            format: ProbeFormat::JsonField("passive_dns"),
+        }
+    }
+
+    async fn passive_dns(&self, domain: &str, subdomains: &mut HashSet<String>) -> Result<usize, SourceError> {
+        let url = format!("{}/{}/passive_dns", API_URL, domain);
+        let response = check_status(self.get(&url).send().await?)?;
+        let data: Response = parse_json(&response.text().await?)?;
+
         let mut results = 0;
-        let mut errors = 0;
+        for entry in data.passive_dns {
+            let hostname = entry.hostname.to_lowercase();
+            if is_valid_subdomain(&hostname, domain) && subdomains.insert(hostname) {
+                results += 1;
+            }
+        }
+        Ok(results)
+    }
 
-        debug!("Querying AlienVault for domain: {}", domain);
-        
-        let url = format!(
-            "https://otx.alienvault.com/api/v1/indicators/domain/{}/passive_dns",
-            domain
-        );
-
-        let response = match self.client
-            .get(&url)
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("AlienVault returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
+    async fn url_list(&self, domain: &str, subdomains: &mut HashSet<String>) -> Result<usize, SourceError> {
+        let mut results = 0;
+
+        for page in 1..=MAX_URL_LIST_PAGES {
+            let url = format!("{}/{}/url_list?limit={}&page={}", API_URL, domain, URL_LIST_LIMIT, page);
+            // Names from earlier pages are already in `subdomains`, so a
+            // failing page just ends the list
+            let response = check_status(self.get(&url).send().await?)?;
+            let data: UrlListResponse = parse_json(&response.text().await?)?;
+
+            for entry in data.url_list {
+                // Older entries leave hostname empty, so fall back to the URL
+                let hostname = if entry.hostname.is_empty() {
+                    match Url::parse(&entry.url).ok().and_then(|url| url.host_str().map(String::from)) {
+                        Some(host) => host,
+                        None => continue,
+                    }
+                } else {
+                    entry.hostname
+                };
+                let hostname = hostname.to_lowercase();
+                if is_valid_subdomain(&hostname, domain) && subdomains.insert(hostname) {
+                    results += 1;
                 }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query AlienVault: {}", e);
-                return Ok(HashSet::new());
             }
-        };
 
-        let data: Response = match response
-            .json()
-            .await
-        {
-            Ok(d) => d,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse AlienVault response: {}", e);
-                return Ok(HashSet::new());
+            if !data.has_next {
+                break;
             }
-        };
+        }
+
+        Ok(results)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        debug!("Querying AlienVault for domain: {}", domain);
 
         let mut subdomains = HashSet::new();
-        for entry in data.passive_dns {
-            let hostname = entry.hostname.to_lowercase();
-            if is_valid_subdomain(&hostname, domain) {
-                results += 1;
-                subdomains.insert(hostname);
+        let mut last_error = None;
+
+        match self.passive_dns(domain, &mut subdomains).await {
+            Ok(found) => results += found,
+            Err(e) => {
+                warn!("AlienVault passive_dns failed: {}", e);
+                last_error = Some(e);
+            }
+        }
+        match self.url_list(domain, &mut subdomains).await {
+            Ok(found) => results += found,
+            Err(e) => {
+                warn!("AlienVault url_list failed: {}", e);
+                last_error = Some(e);
             }
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "AlienVault finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("AlienVault finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
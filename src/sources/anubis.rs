@@ -1,11 +1,10 @@
-use anyhow::{Context, Result};
-use log::{debug, warn};
+use log::debug;
 use reqwest::Client;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain, is_html_response};
+use crate::sources::{check_status, is_html_response, is_valid_subdomain, Probe, ProbeFormat, SourceError};
 
 #[derive(Clone)]
 pub struct AnubisSource {
@@ -22,42 +21,22 @@ impl AnubisSource {
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(format!("https://jldc.me/anubis/subdomains/{}", domain)),
+            format: ProbeFormat::JsonArray,
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         debug!("Querying Anubis for domain: {}", domain);
 
         let url = format!("https://jldc.me/anubis/subdomains/{}", domain);
-        let response = match self.client
-            .get(&url)
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("Anubis returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
-                }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query Anubis: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        let text = match response.text().await {
-            Ok(t) => t,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to read Anubis response: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
+        let response = check_status(self.client.get(&url).send().await?)?;
+        let text = response.text().await?;
         
         let mut subdomains = HashSet::new();
 
@@ -75,8 +54,7 @@ impl AnubisSource {
             Err(_) => {
                 // If JSON parsing fails, check if it's HTML
                 if is_html_response(&text) {
-                    debug!("Received HTML response from Anubis, skipping...");
-                    return Ok(subdomains);
+                    return Err(SourceError::UpstreamHtml);
                 }
                 // Otherwise try to parse each line as a potential subdomain
                 for line in text.lines() {
@@ -90,10 +68,7 @@ impl AnubisSource {
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "Anubis finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
+        debug!("Anubis finished: {} results in {:?}", results, elapsed);
         Ok(subdomains)
     }
 }
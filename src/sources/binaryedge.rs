@@ -0,0 +1,109 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://api.binaryedge.io/v2/query/domains/subdomain";
+// Every page costs a credit
+const MAX_PAGES: usize = 20;
+
+#[derive(Clone)]
+pub struct BinaryEdgeSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubdomainResponse {
+    #[serde(default)]
+    events: Vec<String>,
+    #[serde(default)]
+    pagesize: usize,
+    #[serde(default)]
+    total: usize,
+}
+
+impl BinaryEdgeSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(format!("{}/{}?page=1", API_URL, domain))
+                .header("X-Key", api_key),
+            format: ProbeFormat::JsonField("events"),
+        })
+    }
+
+    async fn fetch_page(&self, api_key: &str, domain: &str, page: usize) -> Result<SubdomainResponse, SourceError> {
+        let response = check_status(
+            self.client
+                .get(format!("{}/{}?page={}", API_URL, domain, page))
+                .header("X-Key", api_key)
+                .send()
+                .await?,
+        )?;
+        parse_json(&response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying BinaryEdge API for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        for page in 1..=MAX_PAGES {
+            let data = match keys.with_key(|api_key| self.fetch_page(api_key, domain, page)).await {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("BinaryEdge stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            let events = data.events.len();
+            for subdomain in data.events {
+                let subdomain = subdomain.to_lowercase();
+                if is_valid_subdomain(&subdomain, domain) && subdomains.insert(subdomain) {
+                    results += 1;
+                }
+            }
+
+            if events == 0 || page * data.pagesize.max(1) >= data.total {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("BinaryEdge finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
@@ -1,4 +1,3 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
@@ -7,7 +6,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, Probe, ProbeFormat, SourceError};
 
 const MAX_RETRIES: u32 = 3;
 const RETRY_DELAY: Duration = Duration::from_secs(2);
@@ -39,7 +38,14 @@ impl BufferOverSource {
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(format!("https://dns.bufferover.run/dns?q=.{}", domain)),
+            format: ProbeFormat::JsonField("FDNS_A"),
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
         let mut errors = 0;
@@ -81,9 +87,9 @@ impl BufferOverSource {
         let data = match final_data {
             Some(d) => d,
             None => {
-                warn!("Failed to query all BufferOver endpoints: {}", 
-                    last_error.unwrap_or_else(|| anyhow::anyhow!("Unknown error")));
-                return Ok(HashSet::new());
+                let e = last_error.unwrap_or_else(|| SourceError::Network("no endpoint tried".to_string()));
+                warn!("Failed to query all BufferOver endpoints: {}", e);
+                return Err(e);
             }
         };
 
@@ -108,30 +114,20 @@ impl BufferOverSource {
         Ok(subdomains)
     }
 
-    async fn try_request(&self, url: &str) -> Result<Response> {
-        let response = self.client
-            .get(url)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-            .send()
-            .await
-            .context("Failed to query BufferOver")?;
-
-        let status = response.status();
-        if status.is_server_error() {
-            return Err(anyhow::anyhow!("BufferOver server error: {}", status));
-        }
-
-        if !status.is_success() {
-            return Err(anyhow::anyhow!("BufferOver returned error status: {}", status));
-        }
-
-        let data: Response = response.json().await
-            .context("Failed to parse BufferOver response")?;
+    async fn try_request(&self, url: &str) -> Result<Response, SourceError> {
+        let response = check_status(
+            self.client
+                .get(url)
+                .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
+                .send()
+                .await?,
+        )?;
+        let data: Response = parse_json(&response.text().await?)?;
 
         // Check for API errors
         if let Some(meta) = &data.meta {
             if !meta.error.is_empty() {
-                return Err(anyhow::anyhow!("BufferOver API error: {}", meta.error));
+                return Err(SourceError::Parse(format!("BufferOver API error: {}", meta.error)));
             }
         }
 
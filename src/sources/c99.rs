@@ -0,0 +1,98 @@
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://api.c99.nl/subdomainfinder";
+
+#[derive(Clone)]
+pub struct C99Source {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct C99Response {
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    subdomains: Vec<C99Subdomain>,
+}
+
+#[derive(Debug, Deserialize)]
+struct C99Subdomain {
+    subdomain: String,
+}
+
+impl C99Source {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn url(api_key: &str, domain: &str) -> String {
+        format!("{}?key={}&domain={}&json", API_URL, api_key, domain)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self.client.get(Self::url(api_key, domain)),
+            format: ProbeFormat::JsonField("subdomains"),
+        })
+    }
+
+    async fn fetch(&self, api_key: &str, domain: &str) -> Result<Vec<C99Subdomain>, SourceError> {
+        let response = check_status(self.client.get(Self::url(api_key, domain)).send().await?)?;
+        let data: C99Response = parse_json(&response.text().await?)?;
+        if data.success {
+            return Ok(data.subdomains);
+        }
+        // Failures come back as a 200 with `success: false` and only a
+        // free-text `error`, which says too little to tell a bad key from
+        // a quota; rejected keys are caught by their status code above
+        Err(SourceError::Parse(data.error.unwrap_or_default()))
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying C99 subdomain finder for domain: {}", domain);
+
+        let entries = keys.with_key(|api_key| self.fetch(api_key, domain)).await?;
+
+        let mut subdomains = HashSet::new();
+        for entry in entries {
+            let name = entry.subdomain.to_lowercase();
+            if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                results += 1;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("C99 finished: {} results in {:?}", results, elapsed);
+        Ok(subdomains)
+    }
+}
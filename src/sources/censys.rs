@@ -1,34 +1,75 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
+use serde_json::Value;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, create_client, insert_cert_names, parse_json, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const HOSTS_URL: &str = "https://search.censys.io/api/v2/hosts/search";
+const CERTIFICATES_URL: &str = "https://search.censys.io/api/v2/certificates/search";
+const PER_PAGE: usize = 100;
+
+/// `[censys]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CensysSettings {
+    /// Upper bound on hits read from each index
+    pub max_results: usize,
+    /// Search the host index (names seen on live services)
+    pub hosts: bool,
+    /// Search the certificate index (names in issued certificates)
+    pub certificates: bool,
+}
+
+impl Default for CensysSettings {
+    fn default() -> Self {
+        Self {
+            max_results: 1000,
+            hosts: true,
+            certificates: true,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct CensysSource {
     client: Arc<Client>,
     api_keys: Vec<(String, String)>, // (api_id, api_secret) pairs
+    settings: CensysSettings,
 }
 
 #[derive(Debug, Deserialize)]
 struct CensysResponse {
-    results: Vec<CensysResult>,
+    result: CensysSearch,
+}
+
+#[derive(Debug, Deserialize)]
+struct CensysSearch {
+    hits: Vec<CensysHit>,
+    links: Option<CensysLinks>,
 }
 
 #[derive(Debug, Deserialize)]
-struct CensysResult {
+struct CensysHit {
+    #[serde(default)]
     names: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct CensysLinks {
+    #[serde(default)]
+    next: String,
+}
+
 impl CensysSource {
     pub fn new() -> Self {
         Self {
             client: create_client(),
             api_keys: Vec::new(),
+            settings: CensysSettings::default(),
         }
     }
 
@@ -36,78 +77,123 @@ impl CensysSource {
         self.api_keys.extend(keys);
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn set_settings(&mut self, settings: CensysSettings) {
+        self.settings = settings;
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let (api_id, api_secret) = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .post(HOSTS_URL)
+                .basic_auth(api_id, Some(api_secret))
+                .json(&serde_json::json!({
+                    "q": format!("names: {}", domain),
+                    "per_page": 1,
+                })),
+            format: ProbeFormat::JsonField("result"),
+        })
+    }
+
+    async fn search_page(&self, (api_id, api_secret): &(String, String), url: &str, body: &Value) -> Result<CensysSearch, SourceError> {
+        let response = check_status(
+            self.client
+                .post(url)
+                .basic_auth(api_id, Some(api_secret))
+                .json(body)
+                .send()
+                .await?,
+        )?;
+        let response: CensysResponse = parse_json(&response.text().await?)?;
+        Ok(response.result)
+    }
+
+    /// Follows the `links.next` cursor of one index until it runs out or
+    /// the result cap is reached
+    async fn search_index(&self, keys: &mut KeyRing<'_, (String, String)>, url: &str, body: Value, domain: &str, subdomains: &mut HashSet<String>) -> Result<usize, SourceError> {
+        let mut results = 0;
+        let mut hits_read = 0;
+        let mut cursor = String::new();
+
+        loop {
+            let mut body = body.clone();
+            if !cursor.is_empty() {
+                body["cursor"] = Value::String(cursor.clone());
+            }
+
+            let search = keys.with_key(|key| self.search_page(key, url, &body)).await?;
+
+            hits_read += search.hits.len();
+            for hit in search.hits {
+                for name in hit.names {
+                    results += insert_cert_names(&name, domain, subdomains);
+                }
+            }
+
+            cursor = search.links.map(|links| links.next).unwrap_or_default();
+            if cursor.is_empty() || hits_read >= self.settings.max_results {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         if self.api_keys.is_empty() {
-            warn!("No Censys API keys provided, skipping...");
-            return Ok(HashSet::new());
+            return Err(SourceError::MissingKey);
         }
-
-        // Use first API key pair (could be randomized like virustotal if needed)
-        let (api_id, api_secret) = &self.api_keys[0];
+        // Spread runs over the key pairs instead of always draining the first
+        let mut pairs = self.api_keys.clone();
+        pairs.rotate_left(rand::random::<usize>() % self.api_keys.len());
+        let mut keys = KeyRing::new(&pairs);
 
         debug!("Querying Censys API for domain: {}", domain);
 
-        let url = "https://search.censys.io/api/v2/hosts/search";
         let query = format!("names: {}", domain);
-
-        let response = match self.client
-            .post(url)
-            .basic_auth(api_id, Some(api_secret))
-            .json(&serde_json::json!({
-                "q": query,
-                "per_page": 100,
-                "virtual_hosts": "INCLUDE"
-            }))
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("Censys API returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
-                }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query Censys API: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        let censys_data: CensysResponse = match response
-            .json()
-            .await
-        {
-            Ok(data) => data,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse Censys API response: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
+        let mut searches = Vec::new();
+        if self.settings.hosts {
+            searches.push((
+                HOSTS_URL,
+                serde_json::json!({
+                    "q": query,
+                    "per_page": PER_PAGE,
+                    "virtual_hosts": "INCLUDE"
+                }),
+            ));
+        }
+        if self.settings.certificates {
+            searches.push((
+                CERTIFICATES_URL,
+                serde_json::json!({
+                    "q": query,
+                    "per_page": PER_PAGE
+                }),
+            ));
+        }
 
         let mut subdomains = HashSet::new();
-        for result in censys_data.results {
-            for name in result.names {
-                let name = name.to_lowercase();
-                if is_valid_subdomain(&name, domain) {
-                    results += 1;
-                    subdomains.insert(name);
+        let mut last_error = None;
+        for (url, body) in searches {
+            match self.search_index(&mut keys, url, body, domain, &mut subdomains).await {
+                Ok(found) => results += found,
+                Err(e) => {
+                    warn!("Censys search {} failed: {}", url, e);
+                    last_error = Some(e);
                 }
             }
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "Censys finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("Censys finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
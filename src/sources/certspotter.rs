@@ -1,4 +1,3 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
@@ -6,7 +5,7 @@ use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, is_valid_subdomain, parse_json, partial_result, Probe, ProbeFormat, SourceError};
 
 #[derive(Clone)]
 pub struct CertSpotterSource {
@@ -35,25 +34,51 @@ impl CertSpotterSource {
         self.api_keys.extend(keys);
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(format!(
+                    "https://api.certspotter.com/v1/issuances?domain={}&include_subdomains=true&expand=dns_names",
+                    domain
+                ))
+                .header("Authorization", format!("Bearer {}", api_key)),
+            format: ProbeFormat::JsonArray,
+        })
+    }
+
+    async fn fetch_page(&self, url: &str, api_key: &str) -> Result<Vec<Certificate>, SourceError> {
+        let response = check_status(
+            self.client
+                .get(url)
+                .header("Authorization", format!("Bearer {}", api_key))
+                .send()
+                .await?,
+        )?;
+        parse_json(&response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         debug!("Querying CertSpotter for domain: {}", domain);
 
-        // Check if API keys are available
-        if self.api_keys.is_empty() {
-            warn!("No CertSpotter API keys provided, skipping...");
-            return Ok(HashSet::new());
-        }
-
         // Use first API key (could be randomized like virustotal if needed)
-        let api_key = &self.api_keys[0];
+        let api_key = self.api_keys.first().ok_or(SourceError::MissingKey)?;
 
         let mut subdomains = HashSet::new();
+        let mut last_error = None;
         let mut after_id = None;
 
+        // Pages are fetched one at a time rather than through the page pool:
+        // each `after` cursor is the id of the last issuance on the previous
+        // page, so no page can be requested before the one ahead of it arrives
         loop {
             let mut url = format!(
                 "https://api.certspotter.com/v1/issuances?domain={}&include_subdomains=true&expand=dns_names",
@@ -64,32 +89,11 @@ impl CertSpotterSource {
                 url.push_str(&format!("&after={}", id));
             }
 
-            let response = match self.client
-                .get(&url)
-                .header("Authorization", format!("Bearer {}", api_key))
-                .send()
-                .await
-            {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        errors += 1;
-                        warn!("CertSpotter API returned error status: {}", resp.status());
-                        break;
-                    }
-                    resp
-                }
-                Err(e) => {
-                    errors += 1;
-                    warn!("Failed to query CertSpotter: {}", e);
-                    break;
-                }
-            };
-
-            let certificates: Vec<Certificate> = match response.json().await {
-                Ok(certs) => certs,
+            let certificates = match self.fetch_page(&url, api_key).await {
+                Ok(certificates) => certificates,
                 Err(e) => {
-                    errors += 1;
-                    warn!("Failed to parse CertSpotter response: {}", e);
+                    warn!("CertSpotter pagination stopped early: {}", e);
+                    last_error = Some(e);
                     break;
                 }
             };
@@ -101,9 +105,8 @@ impl CertSpotterSource {
             for cert in &certificates {
                 for hostname in &cert.dns_names {
                     let hostname = hostname.to_lowercase();
-                    if is_valid_subdomain(&hostname, domain) {
+                    if is_valid_subdomain(&hostname, domain) && subdomains.insert(hostname) {
                         results += 1;
-                        subdomains.insert(hostname);
                     }
                 }
             }
@@ -113,10 +116,7 @@ impl CertSpotterSource {
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "CertSpotter finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("CertSpotter finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
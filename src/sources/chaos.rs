@@ -1,11 +1,10 @@
-use anyhow::{Context, Result};
-use log::{debug, warn};
+use log::debug;
 use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, is_valid_subdomain, parse_json, Probe, ProbeFormat, SourceError};
 
 #[derive(Clone)]
 pub struct ChaosSource {
@@ -33,18 +32,27 @@ impl ChaosSource {
         self.api_keys.extend(keys);
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(format!("https://dns.projectdiscovery.io/dns/{}/subdomains", domain))
+                .header("Authorization", api_key),
+            format: ProbeFormat::JsonField("subdomains"),
+        })
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
-
-        if self.api_keys.is_empty() {
-            warn!("No Chaos API keys provided, skipping...");
-            return Ok(HashSet::new());
-        }
 
         // Use first API key (could be randomized like virustotal if needed)
-        let api_key = &self.api_keys[0];
+        let api_key = self.api_keys.first().ok_or(SourceError::MissingKey)?;
 
         debug!("Querying Chaos API for domain: {}", domain);
         
@@ -53,38 +61,14 @@ impl ChaosSource {
             domain
         );
 
-        let response = match self.client
-            .get(&url)
-            .header("Authorization", api_key)
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("Chaos API returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
-                }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query Chaos API: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        let chaos_data: ChaosResponse = match response
-            .json()
-            .await
-        {
-            Ok(data) => data,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse Chaos API response: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
+        let response = check_status(
+            self.client
+                .get(&url)
+                .header("Authorization", api_key)
+                .send()
+                .await?,
+        )?;
+        let chaos_data: ChaosResponse = parse_json(&response.text().await?)?;
 
         let mut subdomains = HashSet::new();
         for subdomain in chaos_data.subdomains {
@@ -97,10 +81,7 @@ impl ChaosSource {
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "Chaos finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
+        debug!("Chaos finished: {} results in {:?}", results, elapsed);
         Ok(subdomains)
     }
 }
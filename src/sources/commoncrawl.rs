@@ -1,4 +1,3 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
@@ -8,13 +7,55 @@ use std::time::Instant;
 use url::Url;
 use chrono::{Datelike, Utc};
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, create_client, for_each_line, is_valid_subdomain, parse_json, partial_result, PagePool, Probe, ProbeFormat, SourceError};
 
 const MAX_YEARS_BACK: i32 = 5;
 
+/// Which CommonCrawl indexes to query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexSelection {
+    /// Newest index of each of the last few years
+    #[default]
+    Yearly,
+    /// Every published index
+    All,
+    /// The `count` newest indexes
+    Last,
+}
+
+/// `[commoncrawl]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CommonCrawlSettings {
+    pub indexes: IndexSelection,
+    /// Number of indexes used by `indexes = "last"`
+    pub count: usize,
+    /// Oldest index to consider, as "YYYY" or "YYYY-WW"
+    pub from: Option<String>,
+    /// Newest index to consider, as "YYYY" or "YYYY-WW"
+    pub to: Option<String>,
+    /// Upper bound on CDX pages fetched per index
+    pub max_pages: Option<usize>,
+}
+
+impl Default for CommonCrawlSettings {
+    fn default() -> Self {
+        Self {
+            indexes: IndexSelection::default(),
+            count: 12,
+            from: None,
+            to: None,
+            max_pages: None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CommonCrawlSource {
     client: Arc<Client>,
+    pages: PagePool,
+    settings: CommonCrawlSettings,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,140 +65,214 @@ struct CommonCrawlIndex {
     api_url: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct PageCount {
+    pages: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct CdxRecord {
+    url: String,
+}
+
+/// Host of one CDX JSON line, when it is in scope. Only the `url` field is
+/// percent-decoded; the rest of the record is left as the index wrote it.
+fn cdx_host(line: &str, domain: &str) -> Option<String> {
+    let record: CdxRecord = serde_json::from_str(line).ok()?;
+    let decoded = urlencoding::decode(&record.url).ok()?;
+    let url = Url::parse(&decoded).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    is_valid_subdomain(&host, domain).then_some(host)
+}
+
+// "CC-MAIN-2023-50" -> "2023-50"; older indexes use other id formats
+fn index_date(id: &str) -> &str {
+    id.trim_start_matches("CC-MAIN-")
+}
+
 impl CommonCrawlSource {
     pub fn new() -> Self {
         Self {
             client: create_client(),
+            pages: PagePool::default(),
+            settings: CommonCrawlSettings::default(),
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
-        let start_time = Instant::now();
-        let mut results = 0;
-        let mut errors = 0;
+    pub fn set_page_pool(&mut self, pages: PagePool) {
+        self.pages = pages;
+    }
 
-        debug!("Querying CommonCrawl for domain: {}", domain);
+    pub fn set_settings(&mut self, settings: CommonCrawlSettings) {
+        self.settings = settings;
+    }
 
-        let mut subdomains = HashSet::new();
+    pub(crate) fn probe(&self, _domain: &str) -> Probe {
+        Probe {
+            request: self.client.get("https://index.commoncrawl.org/collinfo.json"),
+            format: ProbeFormat::JsonArray,
+        }
+    }
 
-        // Get all available indexes
-        let response = match self.client
-            .get("https://index.commoncrawl.org/collinfo.json")
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("CommonCrawl returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
-                }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query CommonCrawl indexes: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        let indices: Vec<CommonCrawlIndex> = match response.json().await {
-            Ok(idx) => idx,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse CommonCrawl indexes: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        // Get current year and filter indexes for last MAX_YEARS_BACK years
-        let current_year = Utc::now().year();
-        let mut search_indexes = HashMap::new();
-        
-        for year in (current_year - MAX_YEARS_BACK..=current_year).rev() {
-            let year_str = year.to_string();
-            for index in &indices {
-                if index.id.contains(&year_str) {
-                    if !search_indexes.contains_key(&year_str) {
-                        search_indexes.insert(year_str.clone(), index.api_url.clone());
-                        break;
+    /// Picks the CDX endpoints to query. `collinfo.json` lists indexes
+    /// newest first.
+    fn select_indexes(&self, indices: &[CommonCrawlIndex]) -> Vec<String> {
+        let settings = &self.settings;
+        let in_range = indices.iter().filter(|index| {
+            let date = index_date(&index.id);
+            let after_from = settings.from.as_deref().is_none_or(|from| date >= from);
+            let before_to = settings.to.as_deref().is_none_or(|to| {
+                date.get(..to.len()).is_some_and(|prefix| prefix <= to)
+            });
+            after_from && before_to
+        });
+
+        match settings.indexes {
+            IndexSelection::All => in_range.map(|index| index.api_url.clone()).collect(),
+            IndexSelection::Last => in_range
+                .take(settings.count)
+                .map(|index| index.api_url.clone())
+                .collect(),
+            IndexSelection::Yearly => {
+                // Get current year and keep the newest index for each of the
+                // last MAX_YEARS_BACK years
+                let current_year = Utc::now().year();
+                let mut search_indexes = HashMap::new();
+                for index in in_range {
+                    for year in (current_year - MAX_YEARS_BACK..=current_year).rev() {
+                        let year_str = year.to_string();
+                        if index.id.contains(&year_str) && !search_indexes.contains_key(&year_str) {
+                            search_indexes.insert(year_str, index.api_url.clone());
+                            break;
+                        }
                     }
                 }
+                search_indexes.into_values().collect()
             }
         }
+    }
 
-        // Query each year's index
-        for api_url in search_indexes.values() {
-            let url = format!("{}?url=*.{}", api_url, domain);
-            
-            let response = match self.client
+    fn cdx_url(api_url: &str, domain: &str) -> String {
+        format!("{}?url=*.{}&output=json&fl=url", api_url, domain)
+    }
+
+    /// Asks an index how many CDX pages the query spans
+    async fn page_count(&self, api_url: &str, domain: &str) -> Result<usize, SourceError> {
+        let url = format!("{}&showNumPages=true", Self::cdx_url(api_url, domain));
+        let response = check_status(
+            self.client
                 .get(&url)
                 .header("Host", "index.commoncrawl.org")
                 .send()
-                .await
-            {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        errors += 1;
-                        warn!("CommonCrawl API returned error status: {}", resp.status());
-                        continue;
-                    }
-                    resp
+                .await?,
+        )?;
+        let count: PageCount = parse_json(&response.text().await?)?;
+        Ok(count.pages)
+    }
+
+    /// Streams one CDX page and returns the in-scope hosts it contains
+    async fn fetch_page(&self, api_url: &str, domain: &str, page: usize) -> Result<HashSet<String>, SourceError> {
+        let url = format!("{}&page={}", Self::cdx_url(api_url, domain), page);
+        let response = check_status(
+            self.client
+                .get(&url)
+                .header("Host", "index.commoncrawl.org")
+                .send()
+                .await?,
+        )?;
+
+        let mut hosts = HashSet::new();
+        for_each_line(response, |line| hosts.extend(cdx_host(line, domain))).await?;
+
+        Ok(hosts)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        debug!("Querying CommonCrawl for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+
+        // Get all available indexes
+        let response = check_status(
+            self.client
+                .get("https://index.commoncrawl.org/collinfo.json")
+                .send()
+                .await?,
+        )?;
+        let indices: Vec<CommonCrawlIndex> = parse_json(&response.text().await?)?;
+        let api_urls = self.select_indexes(&indices);
+        debug!("Querying {} CommonCrawl indexes", api_urls.len());
+
+        // Find out how many pages each index has for this domain
+        let counts = self
+            .pages
+            .fetch_all(api_urls, |api_url| async move {
+                let result = self.page_count(&api_url, domain).await;
+                (api_url, result)
+            })
+            .await;
+
+        let mut last_error = None;
+        let mut jobs = Vec::new();
+        for (api_url, result) in counts {
+            match result {
+                Ok(pages) => {
+                    let pages = self.settings.max_pages.map_or(pages, |max| pages.min(max));
+                    jobs.extend((0..pages).map(|page| (api_url.clone(), page)));
                 }
                 Err(e) => {
-                    errors += 1;
-                    warn!("Failed to query CommonCrawl API: {}", e);
-                    continue;
+                    warn!("CommonCrawl index {} failed: {}", api_url, e);
+                    last_error = Some(e);
                 }
-            };
+            }
+        }
 
-            let text = match response.text().await {
-                Ok(t) => t,
-                Err(e) => {
-                    errors += 1;
-                    warn!("Failed to read CommonCrawl response: {}", e);
-                    continue;
-                }
-            };
+        // Fetch every page of every index concurrently
+        let pages = self
+            .pages
+            .fetch_all(jobs, |(api_url, page)| async move {
+                let result = self.fetch_page(&api_url, domain, page).await;
+                (api_url, page, result)
+            })
+            .await;
 
-            // Process each line
-            for line in text.lines() {
-                if line.is_empty() {
-                    continue;
+        for (api_url, page, result) in pages {
+            match result {
+                Ok(hosts) => {
+                    results += hosts.len();
+                    subdomains.extend(hosts);
                 }
-
-                // URL decode the line
-                let decoded = match urlencoding::decode(line) {
-                    Ok(d) => d.to_string(),
-                    Err(_) => continue,
-                };
-
-                // Extract and process URLs from the line
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&decoded) {
-                    if let Some(url_str) = json["url"].as_str() {
-                        if let Ok(url) = Url::parse(url_str) {
-                            if let Some(host_str) = url.host_str() {
-                                let host = host_str.to_lowercase();
-                                let host = host.trim_start_matches("25") // Fix for triple encoded URLs
-                                    .trim_start_matches("2f")
-                                    .to_string();
-                                
-                                if is_valid_subdomain(&host, domain) {
-                                    results += 1;
-                                    subdomains.insert(host);
-                                }
-                            }
-                        }
-                    }
+                Err(e) => {
+                    warn!("CommonCrawl index {} page {} failed: {}", api_url, page, e);
+                    last_error = Some(e);
                 }
             }
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "CommonCrawl finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("CommonCrawl finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cdx_host_decodes_only_the_url_field() {
+        let domain = "example.com";
+        let hosts: HashSet<String> = include_str!("../../tests/fixtures/commoncrawl/cdx.jsonl")
+            .lines()
+            .filter_map(|line| cdx_host(line, domain))
+            .collect();
+        let expected: HashSet<String> = ["www.example.com", "api.example.com", "mail.example.com"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(hosts, expected);
     }
 }
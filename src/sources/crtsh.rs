@@ -1,16 +1,71 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tokio_postgres::NoTls;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{create_client, insert_cert_names, parse_json, Probe, ProbeFormat, SourceError};
+
+/// crt.sh's public read-only database
+const CRTSH_POSTGRES: &str = "host=crt.sh port=5432 user=guest dbname=certwatch connect_timeout=30";
+
+/// How long the JSON API gets in auto mode, leaving the SQL fallback most
+/// of the source's budget
+const AUTO_JSON_TIMEOUT: Duration = Duration::from_secs(25);
+
+// Matching on the reversed name lets Postgres use crt.sh's index on
+// reverse(lower(NAME_VALUE)) as a prefix scan
+const IDENTITY_QUERY: &str = "SELECT DISTINCT ci.NAME_VALUE
+    FROM certificate_and_identities ci
+    WHERE reverse(lower(ci.NAME_VALUE)) LIKE reverse(lower($1))";
+
+const UNEXPIRED_IDENTITY_QUERY: &str = "SELECT DISTINCT ci.NAME_VALUE
+    FROM certificate_and_identities ci
+    WHERE reverse(lower(ci.NAME_VALUE)) LIKE reverse(lower($1))
+      AND x509_notAfter(ci.CERTIFICATE) >= now() AT TIME ZONE 'UTC'";
+
+/// How crt.sh is queried
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CrtShBackend {
+    /// JSON API first, falling back to SQL when it fails. The fallback only
+    /// runs one way: by the time SQL is tried the JSON API has already
+    /// failed for this domain, so going back to it would repeat that request
+    #[default]
+    Auto,
+    /// Only the JSON API
+    Json,
+    /// Only the PostgreSQL interface
+    Sql,
+}
+
+/// `[crtsh]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrtShSettings {
+    pub backend: CrtShBackend,
+    /// Skip certificates whose not_after is in the past
+    pub exclude_expired: bool,
+    /// libpq-style connection string for the SQL backend
+    pub postgres: String,
+}
+
+impl Default for CrtShSettings {
+    fn default() -> Self {
+        Self {
+            backend: CrtShBackend::default(),
+            exclude_expired: false,
+            postgres: CRTSH_POSTGRES.to_string(),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct CrtShSource {
     client: Arc<Client>,
+    settings: CrtShSettings,
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,88 +80,148 @@ impl CrtShSource {
     pub fn new() -> Self {
         Self {
             client: create_client(),
+            settings: CrtShSettings::default(),
         }
     }
 
-    fn process_name(&self, name: &str, domain: &str, subdomains: &mut HashSet<String>) -> usize {
-        let mut count = 0;
-        for name in name.split('\n') {
-            let name = name.trim()
-                .trim_start_matches("*.")
-                .trim_start_matches('.')
-                .to_lowercase();
-            
-            if !name.is_empty() && is_valid_subdomain(&name, domain) {
-                count += 1;
-                subdomains.insert(name);
-            }
-        }
-        count
+    pub fn set_settings(&mut self, settings: CrtShSettings) {
+        self.settings = settings;
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
-        let start_time = Instant::now();
-        let mut results = 0;
-        let mut errors = 0;
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(format!("https://crt.sh/?q=%.{}&output=json", domain)),
+            format: ProbeFormat::JsonArray,
+        }
+    }
 
-        debug!("Querying crt.sh for domain: {}", domain);
-        
-        let url = format!(
-            "https://crt.sh/?q=%.{}&output=json",
+    async fn query_json(&self, domain: &str, timeout: Option<Duration>) -> Result<HashSet<String>, SourceError> {
+        let mut url = format!(
+            "https://crt.sh/?q=%.{}&output=json&deduplicate=Y",
             domain
         );
+        if self.settings.exclude_expired {
+            url.push_str("&exclude=expired");
+        }
 
         // Use connection pooling and keep-alive
-        let response = match self.client
+        let mut request = self.client
             .get(&url)
             .header("Connection", "keep-alive")
-            .header("Keep-Alive", "timeout=60")
-            .send()
-            .await
-        {
-            Ok(resp) => resp,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query crt.sh: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        // First get the response text
-        let text = match response.text().await {
-            Ok(t) => t,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to read crt.sh response: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
+            .header("Keep-Alive", "timeout=60");
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await?;
 
-        // Try to parse as JSON
-        let entries: Vec<CrtShEntry> = match serde_json::from_str(&text) {
-            Ok(e) => e,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse crt.sh response: {} (Response: {})", e, text);
-                return Ok(HashSet::new());
-            }
+        // crt.sh often answers overloaded queries with an HTML error page,
+        // so parse the body before looking at the status
+        let status = response.status();
+        let text = response.text().await?;
+        let entries: Vec<CrtShEntry> = match parse_json(&text) {
+            Ok(entries) => entries,
+            Err(_) if !status.is_success() => return Err(SourceError::from_status(status)),
+            Err(e) => return Err(e),
         };
 
         let mut subdomains = HashSet::new();
         for entry in entries {
             if let Some(name) = entry.name_value {
-                results += self.process_name(&name, domain, &mut subdomains);
+                insert_cert_names(&name, domain, &mut subdomains);
             }
             if let Some(name) = entry.common_name {
-                results += self.process_name(&name, domain, &mut subdomains);
+                insert_cert_names(&name, domain, &mut subdomains);
             }
         }
+        Ok(subdomains)
+    }
+
+    async fn query_sql(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let (client, connection) = tokio_postgres::connect(&self.settings.postgres, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!("crt.sh database connection closed: {}", e);
+            }
+        });
+
+        let query = if self.settings.exclude_expired {
+            UNEXPIRED_IDENTITY_QUERY
+        } else {
+            IDENTITY_QUERY
+        };
+        let pattern = format!("%.{}", domain);
+        let rows = client.query(query, &[&pattern]).await?;
+
+        let mut subdomains = HashSet::new();
+        for row in rows {
+            let name: Option<String> = row.try_get(0)?;
+            if let Some(name) = name {
+                insert_cert_names(&name, domain, &mut subdomains);
+            }
+        }
+        Ok(subdomains)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+
+        debug!("Querying crt.sh for domain: {}", domain);
+
+        let subdomains = match self.settings.backend {
+            CrtShBackend::Json => self.query_json(domain, None).await?,
+            CrtShBackend::Sql => self.query_sql(domain).await?,
+            CrtShBackend::Auto => match self.query_json(domain, Some(AUTO_JSON_TIMEOUT)).await {
+                Ok(subdomains) => subdomains,
+                Err(e) => {
+                    warn!("crt.sh JSON API failed ({}), falling back to SQL", e);
+                    self.query_sql(domain).await?
+                }
+            },
+        };
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "crt.sh finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
+        debug!("crt.sh finished: {} results in {:?}", subdomains.len(), elapsed);
         Ok(subdomains)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the SQL backend against a local Postgres loaded with
+    /// `tests/fixtures/crtsh/schema.sql`. Set `CRTSH_TEST_POSTGRES` to a
+    /// libpq connection string and pass `--ignored` to run it.
+    #[tokio::test]
+    #[ignore = "needs a Postgres server in CRTSH_TEST_POSTGRES"]
+    async fn sql_backend_reads_fixture_identities() {
+        let postgres = std::env::var("CRTSH_TEST_POSTGRES").expect("CRTSH_TEST_POSTGRES is not set");
+
+        let (client, connection) = tokio_postgres::connect(&postgres, NoTls).await.unwrap();
+        tokio::spawn(connection);
+        client
+            .batch_execute(include_str!("../../tests/fixtures/crtsh/schema.sql"))
+            .await
+            .unwrap();
+
+        let mut source = CrtShSource::new();
+        let mut settings = CrtShSettings {
+            backend: CrtShBackend::Sql,
+            exclude_expired: false,
+            postgres: format!("{} options='-c search_path=crtsh_fixture'", postgres),
+        };
+        source.set_settings(settings.clone());
+        let names = source.enumerate("example.com").await.unwrap();
+        let expected: HashSet<String> = ["www.example.com", "api.example.com", "mail.example.com", "old.example.com"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(names, expected);
+
+        settings.exclude_expired = true;
+        source.set_settings(settings);
+        let names = source.enumerate("example.com").await.unwrap();
+        assert!(!names.contains("old.example.com"));
+        assert_eq!(names.len(), 3);
+    }
+}
@@ -0,0 +1,545 @@
+use log::{debug, warn};
+use regex::Regex;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
+use url::Url;
+
+use crate::sources::{
+    check_status, create_client, is_html_response, is_valid_subdomain, parse_json, partial_result, subdomain_pattern,
+    KeyRing, Probe, ProbeFormat, SourceError, SourceProvider,
+};
+
+/// How the body of a declarative source is read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    /// JSON, narrowed down with `pointer` and `field`
+    #[default]
+    Json,
+    /// One entry per line
+    Lines,
+    /// Comma separated, hostnames in `column`
+    Csv,
+    /// Anything else; the pattern runs over the whole body
+    Text,
+}
+
+/// Paging rules of a declarative source
+///
+/// A URL with `{page}` counts up from `start` by `step`; `next` instead
+/// follows a JSON pointer to the next page's URL, or to a cursor that is
+/// put in place of `{cursor}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeclarativePagination {
+    pub start: usize,
+    pub step: usize,
+    pub max_pages: usize,
+    pub next: Option<String>,
+}
+
+impl Default for DeclarativePagination {
+    fn default() -> Self {
+        Self {
+            start: 1,
+            step: 1,
+            max_pages: 10,
+            next: None,
+        }
+    }
+}
+
+/// One `[[declarative]]` entry of the config file
+///
+/// ```toml
+/// [[declarative]]
+/// name = "jldc"
+/// url = "https://jldc.me/anubis/subdomains/{domain}"
+/// format = "json"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeclarativeSettings {
+    pub name: String,
+    /// Request URL; `{domain}`, `{key}`, `{page}` and `{cursor}` are filled in
+    pub url: String,
+    /// Extra request headers, templated like the URL
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Keys file entry holding the key (a string or a list to rotate)
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub format: ResponseFormat,
+    /// JSON pointer to the part of the body holding the names
+    #[serde(default)]
+    pub pointer: String,
+    /// JSON pointer into each array item, e.g. `/hostname`
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Zero-based CSV column
+    #[serde(default)]
+    pub column: usize,
+    /// Pattern picking names out of each entry, `{domain}` is the escaped
+    /// target; the first capture group is used when there is one
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub pagination: DeclarativePagination,
+    #[serde(default = "default_true")]
+    pub default: bool,
+    #[serde(default = "default_true")]
+    pub fast: bool,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl DeclarativeSettings {
+    /// Catches definitions that could never work before a run starts
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("invalid source name '{}'", self.name));
+        }
+        if SourceProvider::is_builtin(&self.name) {
+            return Err(format!("'{}' is the name of a built-in source", self.name));
+        }
+        if !self.url.contains("{domain}") {
+            return Err(format!("{}: url has no {{domain}} placeholder", self.name));
+        }
+        let templated_key = self.url.contains("{key}") || self.headers.values().any(|v| v.contains("{key}"));
+        if templated_key && self.key.is_none() {
+            return Err(format!("{}: uses {{key}} but names no `key` entry", self.name));
+        }
+        if self.pagination.next.is_some() && self.format != ResponseFormat::Json {
+            return Err(format!("{}: `next` paging needs the json format", self.name));
+        }
+        if self.pagination.step == 0 {
+            return Err(format!("{}: pagination step must be positive", self.name));
+        }
+        if let Some(pattern) = &self.regex {
+            Regex::new(&pattern.replace("{domain}", &regex::escape("example.com")))
+                .map_err(|e| format!("{}: invalid regex: {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    fn paginated(&self) -> bool {
+        self.pagination.next.is_some() || self.url.contains("{page}")
+    }
+}
+
+/// A source described entirely by config: one templated GET per page,
+/// names pulled out of the body by pointer, column or pattern
+#[derive(Clone)]
+pub struct DeclarativeSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+    definition: Arc<DeclarativeSettings>,
+}
+
+/// Where the next page comes from
+enum NextPage {
+    Url(String),
+    Cursor(String),
+}
+
+// Whether both URLs point at the same scheme, host and port
+fn same_origin(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+fn render(template: &str, domain: &str, key: &str, page: usize, cursor: &str) -> String {
+    template
+        .replace("{domain}", domain)
+        .replace("{key}", key)
+        .replace("{page}", &page.to_string())
+        .replace("{cursor}", &urlencoding::encode(cursor))
+}
+
+impl DeclarativeSource {
+    pub fn new(definition: DeclarativeSettings) -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+            definition: Arc::new(definition),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    pub fn requires_key(&self) -> bool {
+        self.definition.key.is_some()
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.definition.default
+    }
+
+    pub fn is_fast(&self) -> bool {
+        self.definition.fast
+    }
+
+    pub fn supports_recursive(&self) -> bool {
+        self.definition.recursive
+    }
+
+    fn request(&self, url: &str, key: &str, domain: &str, page: usize, cursor: &str) -> reqwest::RequestBuilder {
+        let mut request = self.client.get(url);
+        for (name, value) in &self.definition.headers {
+            request = request.header(name.as_str(), render(value, domain, key, page, cursor));
+        }
+        request
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let key = match &self.definition.key {
+            Some(_) => self.api_keys.first()?.as_str(),
+            None => "",
+        };
+        let page = self.definition.pagination.start;
+        let url = render(&self.definition.url, domain, key, page, "");
+        let format = match self.definition.format {
+            ResponseFormat::Json => ProbeFormat::Json,
+            _ => ProbeFormat::Text,
+        };
+        Some(Probe {
+            request: self.request(&url, key, domain, page, ""),
+            format,
+        })
+    }
+
+    async fn fetch(
+        &self,
+        key: &str,
+        domain: &str,
+        page: usize,
+        next: Option<&NextPage>,
+    ) -> Result<(String, Option<Value>), SourceError> {
+        let (url, cursor) = match next {
+            Some(NextPage::Url(url)) => (url.clone(), ""),
+            Some(NextPage::Cursor(cursor)) => (render(&self.definition.url, domain, key, page, cursor), cursor.as_str()),
+            None => (render(&self.definition.url, domain, key, page, ""), ""),
+        };
+        // A next URL comes from the response body, so the headers (and the
+        // key in them) only go along while it stays on the configured host
+        let request = match next {
+            Some(NextPage::Url(_)) if !same_origin(&url, &render(&self.definition.url, domain, key, page, "")) => {
+                debug!("{} next page {} leaves the configured host, sending no headers", self.definition.name, url);
+                self.client.get(&url)
+            }
+            _ => self.request(&url, key, domain, page, cursor),
+        };
+        let response = check_status(request.send().await?)?;
+        let text = response.text().await?;
+        if self.definition.format != ResponseFormat::Text && is_html_response(&text) {
+            return Err(SourceError::UpstreamHtml);
+        }
+        let json = match self.definition.format {
+            ResponseFormat::Json => Some(parse_json(&text)?),
+            _ => None,
+        };
+        Ok((text, json))
+    }
+
+    /// Splits a body into the pieces the pattern runs over
+    fn entries(&self, body: &str, json: Option<&Value>) -> Vec<String> {
+        match self.definition.format {
+            ResponseFormat::Json => {
+                let Some(selected) = json.and_then(|json| json.pointer(&self.definition.pointer)) else {
+                    return Vec::new();
+                };
+                let pick = |item: &Value| -> Option<String> {
+                    let item = match &self.definition.field {
+                        Some(field) => item.pointer(field)?,
+                        None => item,
+                    };
+                    Some(match item {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    })
+                };
+                match selected {
+                    Value::Array(items) => items.iter().filter_map(pick).collect(),
+                    other => pick(other).into_iter().collect(),
+                }
+            }
+            ResponseFormat::Lines => body.lines().map(str::to_string).collect(),
+            ResponseFormat::Csv => body
+                .lines()
+                .filter_map(|line| line.split(',').nth(self.definition.column))
+                .map(|cell| cell.trim().trim_matches('"').to_string())
+                .collect(),
+            ResponseFormat::Text => vec![body.to_string()],
+        }
+    }
+
+    fn next_page(&self, json: Option<&Value>) -> Option<NextPage> {
+        let pointer = self.definition.pagination.next.as_ref()?;
+        let next = match json?.pointer(pointer)? {
+            Value::String(s) if !s.is_empty() => s.clone(),
+            Value::Number(n) => n.to_string(),
+            _ => return None,
+        };
+        if next.starts_with("http://") || next.starts_with("https://") {
+            Some(NextPage::Url(next))
+        } else {
+            Some(NextPage::Cursor(next))
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+        let definition = &self.definition;
+
+        if definition.key.is_some() && self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        let pattern = match &definition.regex {
+            Some(pattern) => Regex::new(&pattern.replace("{domain}", &regex::escape(domain)))
+                .map_err(|e| SourceError::Parse(format!("invalid regex: {}", e)))?,
+            None => subdomain_pattern(domain),
+        };
+
+        debug!("Querying declarative source {} for domain: {}", definition.name, domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+        let mut next = None;
+
+        for index in 0..definition.pagination.max_pages.max(1) {
+            let page = definition.pagination.start + index * definition.pagination.step;
+            let fetched = if definition.key.is_some() {
+                keys.with_key(|key| self.fetch(key, domain, page, next.as_ref())).await
+            } else {
+                self.fetch("", domain, page, next.as_ref()).await
+            };
+            let (body, json) = match fetched {
+                Ok(fetched) => fetched,
+                Err(e) => {
+                    warn!("{} stopped at page {}: {}", definition.name, page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            let mut found = 0;
+            for entry in self.entries(&body, json.as_ref()) {
+                for captures in pattern.captures_iter(&entry) {
+                    let Some(name) = captures.get(1).or_else(|| captures.get(0)) else {
+                        continue;
+                    };
+                    let name = name.as_str().trim_start_matches("*.").trim_start_matches('.').to_lowercase();
+                    if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                        found += 1;
+                    }
+                }
+            }
+            results += found;
+
+            if !definition.paginated() {
+                break;
+            }
+            if definition.pagination.next.is_some() {
+                next = self.next_page(json.as_ref());
+                if next.is_none() {
+                    break;
+                }
+            } else if found == 0 {
+                // Numbered pages have no end marker; a page with nothing new is the end
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("{} finished: {} results in {:?}", definition.name, results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::mock::{serve, MockResponse};
+    use std::sync::Mutex;
+
+    fn definition(toml: &str) -> DeclarativeSettings {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn names(source: &DeclarativeSource, body: &str) -> Vec<String> {
+        let json = match source.definition.format {
+            ResponseFormat::Json => Some(serde_json::from_str(body).unwrap()),
+            _ => None,
+        };
+        source.entries(body, json.as_ref())
+    }
+
+    #[test]
+    fn entries_follow_the_format() {
+        let json = DeclarativeSource::new(definition(
+            "name = \"items\"\nurl = \"https://api.example.net/{domain}\"\npointer = \"/data/items\"\nfield = \"/hostname\"",
+        ));
+        assert_eq!(
+            names(&json, include_str!("../../tests/fixtures/declarative/items.json")),
+            vec!["www.example.com", "API.example.com", "*.cdn.example.com", "www.notexample.com"]
+        );
+
+        let lines = DeclarativeSource::new(definition(
+            "name = \"lines\"\nurl = \"https://api.example.net/{domain}\"\nformat = \"lines\"",
+        ));
+        assert_eq!(
+            names(&lines, include_str!("../../tests/fixtures/declarative/hosts.txt")),
+            vec!["www.example.com", "mail.example.com", "", "www.notexample.com"]
+        );
+
+        let csv = DeclarativeSource::new(definition(
+            "name = \"csv\"\nurl = \"https://api.example.net/{domain}\"\nformat = \"csv\"\ncolumn = 1",
+        ));
+        assert_eq!(
+            names(&csv, include_str!("../../tests/fixtures/declarative/hosts.csv")),
+            vec!["hostname", "vpn.example.com", "dev.example.com", "www.notexample.com"]
+        );
+    }
+
+    #[test]
+    fn next_pointer_gives_a_url_or_a_cursor() {
+        let source = DeclarativeSource::new(definition(
+            "name = \"paged\"\nurl = \"https://api.example.net/{domain}?cursor={cursor}\"\npointer = \"/results\"\n\
+             pagination = { next = \"/meta/next\" }",
+        ));
+        let next = |body: &str| source.next_page(Some(&serde_json::from_str(body).unwrap()));
+
+        assert!(matches!(next(r#"{"meta": {"next": "https://api.example.net/page/2"}}"#), Some(NextPage::Url(url)) if url == "https://api.example.net/page/2"));
+        assert!(matches!(next(r#"{"meta": {"next": "abc"}}"#), Some(NextPage::Cursor(cursor)) if cursor == "abc"));
+        assert!(matches!(next(r#"{"meta": {"next": 42}}"#), Some(NextPage::Cursor(cursor)) if cursor == "42"));
+        assert!(next(r#"{"meta": {"next": ""}}"#).is_none());
+        assert!(next(r#"{"meta": {}}"#).is_none());
+    }
+
+    #[test]
+    fn validate_catches_broken_definitions() {
+        let check = |toml: &str| definition(&format!("name = \"broken\"\n{}", toml)).validate();
+
+        assert!(check("url = \"https://api.example.net/{domain}\"").is_ok());
+        assert!(check("url = \"https://api.example.net/all\"").unwrap_err().contains("{domain}"));
+        assert!(check("url = \"https://api.example.net/{domain}?key={key}\"").unwrap_err().contains("`key`"));
+        assert!(check("url = \"https://api.example.net/{domain}\"\nformat = \"lines\"\npagination = { next = \"/next\" }")
+            .unwrap_err()
+            .contains("json"));
+        assert!(check("url = \"https://api.example.net/{domain}?page={page}\"\npagination = { step = 0 }")
+            .unwrap_err()
+            .contains("step"));
+        assert!(check("url = \"https://api.example.net/{domain}\"\nregex = \"(\"").unwrap_err().contains("regex"));
+    }
+
+    #[tokio::test]
+    async fn numbered_pages_stop_when_nothing_new_turns_up() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let base = serve(move |request| {
+            seen.lock().unwrap().push(request.target.clone());
+            match request.target.as_str() {
+                "/hosts/example.com?page=1" => MockResponse::new(200, "www.example.com\nmail.example.com\n"),
+                "/hosts/example.com?page=2" => MockResponse::new(200, "vpn.example.com\nwww.example.com\n"),
+                _ => MockResponse::new(200, "www.example.com\n"),
+            }
+        })
+        .await;
+
+        let source = DeclarativeSource::new(definition(&format!(
+            "name = \"numbered\"\nurl = \"{}/hosts/{{domain}}?page={{page}}\"\nformat = \"lines\"",
+            base
+        )));
+        let names = source.enumerate("example.com").await.unwrap();
+        assert_eq!(names.len(), 3);
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_follow_the_next_pointer() {
+        let base = serve(|request| match request.target.as_str() {
+            "/search/example.com?cursor=" => {
+                MockResponse::new(200, include_str!("../../tests/fixtures/declarative/cursor_page1.json"))
+            }
+            "/search/example.com?cursor=c2Vjb25k%2Bpage" => {
+                MockResponse::new(200, include_str!("../../tests/fixtures/declarative/cursor_page2.json"))
+            }
+            _ => MockResponse::new(404, ""),
+        })
+        .await;
+
+        let source = DeclarativeSource::new(definition(&format!(
+            "name = \"cursor\"\nurl = \"{}/search/{{domain}}?cursor={{cursor}}\"\npointer = \"/results\"\n\
+             pagination = {{ next = \"/meta/next\" }}",
+            base
+        )));
+        let names = source.enumerate("example.com").await.unwrap();
+        let expected: HashSet<String> = ["a.example.com", "b.example.com", "c.example.com"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(names, expected);
+    }
+
+    #[tokio::test]
+    async fn next_urls_only_get_the_key_on_the_configured_host() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let seen = keys.clone();
+        let other = serve(move |request| {
+            seen.lock().unwrap().push(("other", request.header("x-api-key").map(str::to_string)));
+            MockResponse::new(200, r#"{"results": ["other.example.com"]}"#)
+        })
+        .await;
+        // Same server, but a host name the source was not configured with
+        let other = other.replace("127.0.0.1", "localhost");
+
+        let seen = keys.clone();
+        let base = serve(move |request| {
+            seen.lock().unwrap().push(("api", request.header("x-api-key").map(str::to_string)));
+            let body = match request.target.as_str() {
+                // Links to the second page by absolute URL on the same host
+                "/v1/example.com" => format!(
+                    r#"{{"results": ["a.example.com"], "next": "http://{}/v1/page2"}}"#,
+                    request.header("host").unwrap_or_default()
+                ),
+                _ => format!(r#"{{"results": ["b.example.com"], "next": "{}/leak"}}"#, other),
+            };
+            MockResponse::new(200, body)
+        })
+        .await;
+
+        let mut source = DeclarativeSource::new(definition(&format!(
+            "name = \"linked\"\nurl = \"{}/v1/{{domain}}\"\nkey = \"linked\"\npointer = \"/results\"\n\
+             headers = {{ x-api-key = \"{{key}}\" }}\npagination = {{ next = \"/next\" }}",
+            base
+        )));
+        source.add_api_keys(vec!["secret".to_string()]);
+        let names = source.enumerate("example.com").await.unwrap();
+
+        assert!(names.contains("other.example.com"));
+        let keys = keys.lock().unwrap();
+        assert!(keys.iter().any(|(host, key)| *host == "api" && key.as_deref() == Some("secret")));
+        assert!(keys.iter().all(|(host, key)| *host != "other" || key.is_none()), "{:?}", *keys);
+    }
+}
@@ -1,4 +1,3 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
@@ -6,24 +5,84 @@ use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, create_client, for_each_line, is_valid_subdomain, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://api.dnsdb.info/dnsdb/v2";
+
+/// Which DNSDB API answers the query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DNSDBSearch {
+    /// Standard `lookup/rrset` with a left-hand wildcard
+    #[default]
+    Lookup,
+    /// Flexible Search over owner names only
+    Flex,
+}
+
+/// `[dnsdb]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DNSDBSettings {
+    pub search: DNSDBSearch,
+    /// Records requested per query
+    pub limit: usize,
+    /// Upper bound on offset continuations after the limit is hit
+    pub max_pages: usize,
+    /// Only names first seen after this time (unix seconds, or negative
+    /// seconds relative to now)
+    pub seen_after: Option<i64>,
+    /// Only names last seen before this time
+    pub seen_before: Option<i64>,
+}
+
+impl Default for DNSDBSettings {
+    fn default() -> Self {
+        Self {
+            search: DNSDBSearch::default(),
+            limit: 10000,
+            max_pages: 10,
+            seen_after: None,
+            seen_before: None,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct DNSDBSource {
     client: Arc<Client>,
     api_keys: Vec<String>,
+    settings: DNSDBSettings,
 }
 
+/// One line of a Streaming API Framing response
 #[derive(Debug, Deserialize)]
-struct DNSDBResponse {
+struct SafRecord {
+    cond: Option<String>,
+    msg: Option<String>,
+    obj: Option<DNSDBRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DNSDBRecord {
     rrname: String,
 }
 
+/// How a SAF stream ended
+enum SafEnd {
+    Succeeded,
+    Limited,
+    Failed(String),
+    /// The connection closed without a terminating condition
+    Truncated,
+}
+
 impl DNSDBSource {
     pub fn new() -> Self {
         Self {
             client: create_client(),
             api_keys: Vec::new(),
+            settings: DNSDBSettings::default(),
         }
     }
 
@@ -31,78 +90,157 @@ impl DNSDBSource {
         self.api_keys.extend(keys);
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
-        let start_time = Instant::now();
-        let mut results = 0;
-        let mut errors = 0;
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
 
-        if self.api_keys.is_empty() {
-            warn!("No DNSDB API keys provided, skipping...");
-            return Ok(HashSet::new());
+    pub fn set_settings(&mut self, settings: DNSDBSettings) {
+        self.settings = settings;
+    }
+
+    fn query_url(&self, domain: &str, limit: usize, offset: usize) -> String {
+        let mut url = match self.settings.search {
+            DNSDBSearch::Lookup => format!("{}/lookup/rrset/name/*.{}/ANY", API_URL, domain),
+            DNSDBSearch::Flex => format!("{}/glob/rrnames/*.{}./ANY", API_URL, domain),
+        };
+        url.push_str(&format!("?limit={}", limit));
+        if offset > 0 {
+            url.push_str(&format!("&offset={}", offset));
         }
+        if let Some(after) = self.settings.seen_after {
+            url.push_str(&format!("&time_first_after={}", after));
+        }
+        if let Some(before) = self.settings.seen_before {
+            url.push_str(&format!("&time_last_before={}", before));
+        }
+        url
+    }
 
-        // Use first API key (could be randomized like virustotal if needed)
-        let api_key = &self.api_keys[0];
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(self.query_url(domain, 1, 0))
+                .header("X-API-Key", api_key)
+                .header("Accept", "application/x-ndjson"),
+            format: ProbeFormat::Text,
+        })
+    }
 
-        debug!("Querying DNSDB API for domain: {}", domain);
+    /// Streams one query, returning the number of records read, the names
+    /// they held and how the stream ended
+    async fn fetch_page(&self, api_key: &str, domain: &str, offset: usize) -> Result<(usize, HashSet<String>, SafEnd), SourceError> {
+        let response = check_status(
+            self.client
+                .get(self.query_url(domain, self.settings.limit, offset))
+                .header("X-API-Key", api_key)
+                .header("Accept", "application/x-ndjson")
+                .send()
+                .await?,
+        )?;
+
+        let mut records = 0;
+        let mut names = HashSet::new();
+        let mut end = SafEnd::Truncated;
+        let mut parse_error = None;
+
+        for_each_line(response, |line| {
+            if line.is_empty() {
+                return;
+            }
+            let record = match serde_json::from_str::<SafRecord>(line) {
+                Ok(record) => record,
+                Err(e) => {
+                    parse_error.get_or_insert(e.to_string());
+                    return;
+                }
+            };
 
-        let url = format!(
-            "https://api.dnsdb.info/lookup/rrset/name/*.{}*/ANY",
-            domain
-        );
-
-        let response = match self.client
-            .get(&url)
-            .header("X-API-Key", api_key)
-            .header("Accept", "application/json")
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("DNSDB API returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
+            if let Some(obj) = record.obj {
+                records += 1;
+                let subdomain = obj.rrname
+                    .trim_end_matches('.')
+                    .trim_start_matches("*.")
+                    .to_lowercase();
+                if is_valid_subdomain(&subdomain, domain) {
+                    names.insert(subdomain);
                 }
-                resp
             }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query DNSDB API: {}", e);
-                return Ok(HashSet::new());
+
+            // "begin" and "ongoing" are keepalives
+            match record.cond.as_deref() {
+                Some("succeeded") => end = SafEnd::Succeeded,
+                Some("limited") => end = SafEnd::Limited,
+                Some("failed") => end = SafEnd::Failed(record.msg.unwrap_or_default()),
+                _ => {}
             }
-        };
+        })
+        .await?;
 
-        let text = match response.text().await {
-            Ok(t) => t,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to read DNSDB response: {}", e);
-                return Ok(HashSet::new());
+        // Lines that are not SAF at all mean the API changed under us
+        if records == 0 {
+            if let Some(e) = parse_error {
+                return Err(SourceError::Parse(e));
             }
-        };
+        }
+        Ok((records, names, end))
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying DNSDB API for domain: {}", domain);
 
         let mut subdomains = HashSet::new();
+        let mut last_error = None;
+        let mut offset = 0;
 
-        // DNSDB returns one JSON object per line
-        for line in text.lines() {
-            if let Ok(record) = serde_json::from_str::<DNSDBResponse>(line) {
-                let subdomain = record.rrname
-                    .trim_end_matches('.')
-                    .trim_start_matches("*.")
-                    .to_lowercase();
-                if is_valid_subdomain(&subdomain, domain) {
+        for page in 0..self.settings.max_pages.max(1) {
+            let (records, names, end) = match keys.with_key(|api_key| self.fetch_page(api_key, domain, offset)).await {
+                Ok(page_result) => page_result,
+                Err(e) => {
+                    warn!("DNSDB stopped at offset {}: {}", offset, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+            for name in names {
+                if subdomains.insert(name) {
                     results += 1;
-                    subdomains.insert(subdomain);
+                }
+            }
+
+            match end {
+                SafEnd::Succeeded => break,
+                // The result limit was hit, continue where this page stopped
+                SafEnd::Limited if records > 0 => {
+                    offset += records;
+                    debug!("DNSDB page {} limited, continuing at offset {}", page, offset);
+                }
+                SafEnd::Limited => break,
+                SafEnd::Failed(msg) => {
+                    warn!("DNSDB query failed: {}", msg);
+                    last_error = Some(SourceError::Parse(msg));
+                    break;
+                }
+                SafEnd::Truncated => {
+                    // The connection dropped mid-stream, so the page is incomplete
+                    warn!("DNSDB stream ended without a terminating condition");
+                    last_error = Some(SourceError::Network("stream ended without a terminating condition".to_string()));
+                    break;
                 }
             }
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "DNSDB finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("DNSDB finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
@@ -0,0 +1,154 @@
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+
+use crate::sources::is_html_response;
+
+/// Why a source failed to produce results
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum SourceError {
+    /// The source needs an API key and none is configured
+    MissingKey,
+    /// No response at all: DNS, connect, TLS or a dropped connection
+    Network(String),
+    /// The upstream answered with an unexpected status code
+    HttpStatus(u16),
+    /// The key was rejected (401/403)
+    Auth(u16),
+    /// The upstream refused because of rate limits or an exhausted quota
+    RateLimited,
+    /// The body could not be parsed into the expected shape
+    Parse(String),
+    /// An HTML page came back where an API response was expected
+    UpstreamHtml,
+    /// The source did not finish within its time budget
+    Timeout,
+    /// A WASM plugin failed to load, trapped or reported an error
+    Plugin(String),
+    /// A local dataset could not be read
+    Io(String),
+}
+
+impl SourceError {
+    /// Maps a non-success status code to the matching error
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => SourceError::Auth(status.as_u16()),
+            StatusCode::TOO_MANY_REQUESTS => SourceError::RateLimited,
+            _ => SourceError::HttpStatus(status.as_u16()),
+        }
+    }
+
+    /// Whether the error points at a broken source rather than missing
+    /// configuration
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, SourceError::MissingKey)
+    }
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::MissingKey => write!(f, "no API key configured"),
+            SourceError::Network(e) => write!(f, "network error: {}", e),
+            SourceError::HttpStatus(status) => write!(f, "HTTP status {}", status),
+            SourceError::Auth(status) => write!(f, "authentication failed (HTTP {})", status),
+            SourceError::RateLimited => write!(f, "rate limited or quota exceeded"),
+            SourceError::Parse(e) => write!(f, "failed to parse response: {}", e),
+            SourceError::UpstreamHtml => write!(f, "upstream returned an HTML page"),
+            SourceError::Timeout => write!(f, "timed out"),
+            SourceError::Plugin(e) => write!(f, "plugin failed: {}", e),
+            SourceError::Io(e) => write!(f, "failed to read dataset: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+impl From<reqwest::Error> for SourceError {
+    fn from(e: reqwest::Error) -> Self {
+        // The URL can carry an API key in its query string, and these
+        // messages end up in logs and saved reports
+        let e = e.without_url();
+        if e.is_timeout() {
+            SourceError::Timeout
+        } else if e.is_decode() {
+            SourceError::Parse(e.to_string())
+        } else if let Some(status) = e.status() {
+            SourceError::from_status(status)
+        } else {
+            SourceError::Network(e.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for SourceError {
+    fn from(e: serde_json::Error) -> Self {
+        SourceError::Parse(e.to_string())
+    }
+}
+
+impl From<tokio_postgres::Error> for SourceError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        // A database error means we got an answer the query did not expect;
+        // anything else is a connection problem
+        if e.as_db_error().is_some() {
+            SourceError::Parse(e.to_string())
+        } else {
+            SourceError::Network(e.to_string())
+        }
+    }
+}
+
+impl From<std::io::Error> for SourceError {
+    fn from(e: std::io::Error) -> Self {
+        SourceError::Io(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for SourceError {
+    fn from(e: rusqlite::Error) -> Self {
+        SourceError::Io(e.to_string())
+    }
+}
+
+/// Passes successful responses through and turns the rest into errors
+pub(crate) fn check_status(response: Response) -> Result<Response, SourceError> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        Err(SourceError::from_status(status))
+    }
+}
+
+/// Parses a JSON body, telling HTML error pages apart from schema changes
+pub(crate) fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T, SourceError> {
+    serde_json::from_str(text).map_err(|e| {
+        if is_html_response(text) {
+            SourceError::UpstreamHtml
+        } else {
+            SourceError::Parse(e.to_string())
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn request_errors_leave_the_url_out() {
+        // Nothing listens on port 1, so the request fails before any response
+        let error = reqwest::Client::new()
+            .get("http://127.0.0.1:1/api?key=hunter2")
+            .send()
+            .await
+            .expect_err("port 1 answered");
+        let error = SourceError::from(error);
+        assert!(matches!(error, SourceError::Network(_)));
+        assert!(!error.to_string().contains("hunter2"), "{}", error);
+    }
+}
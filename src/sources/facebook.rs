@@ -0,0 +1,116 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, insert_cert_names, parse_json, partial_result, Probe, ProbeFormat, SourceError};
+
+const GRAPH_URL: &str = "https://graph.facebook.com/v18.0/certificates";
+// Each page holds up to 1000 certificates; big domains have hundreds of pages
+const MAX_PAGES: usize = 50;
+
+#[derive(Clone)]
+pub struct FacebookSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>, // "app_id|app_secret" access tokens
+}
+
+#[derive(Debug, Deserialize)]
+struct CertificatesResponse {
+    data: Vec<Certificate>,
+    paging: Option<Paging>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Certificate {
+    #[serde(default)]
+    domains: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Paging {
+    next: Option<String>,
+}
+
+impl FacebookSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn first_page_url(domain: &str, access_token: &str, limit: usize) -> String {
+        format!(
+            "{}?query={}&fields=domains&limit={}&access_token={}",
+            GRAPH_URL,
+            domain,
+            limit,
+            urlencoding::encode(access_token)
+        )
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let access_token = self.api_keys.first()?;
+        Some(Probe {
+            request: self.client.get(Self::first_page_url(domain, access_token, 1)),
+            format: ProbeFormat::JsonField("data"),
+        })
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        let access_token = self.api_keys.first().ok_or(SourceError::MissingKey)?;
+
+        debug!("Querying Facebook CT for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+        // paging.next is a complete URL with the cursor and token already in it
+        let mut url = Some(Self::first_page_url(domain, access_token, 1000));
+
+        for page in 0..MAX_PAGES {
+            let Some(page_url) = url.take() else { break };
+            let data: CertificatesResponse = match async {
+                let response = check_status(self.client.get(&page_url).send().await?)?;
+                parse_json(&response.text().await?)
+            }
+            .await
+            {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Facebook CT stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            for cert in &data.data {
+                for name in &cert.domains {
+                    results += insert_cert_names(name, domain, &mut subdomains);
+                }
+            }
+
+            if data.data.is_empty() {
+                break;
+            }
+            url = data.paging.and_then(|paging| paging.next);
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("Facebook CT finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
@@ -0,0 +1,173 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+use url::Url;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://fofa.info/api/v1/search/all";
+const PAGE_SIZE: usize = 10000;
+const MAX_PAGES: usize = 5;
+
+#[derive(Clone)]
+pub struct FofaSource {
+    client: Arc<Client>,
+    api_keys: Vec<(String, String)>, // (email, key) pairs; newer accounts leave email empty
+}
+
+#[derive(Debug, Deserialize)]
+struct FofaResponse {
+    #[serde(default)]
+    error: bool,
+    #[serde(default)]
+    errmsg: String,
+    #[serde(default)]
+    size: usize,
+    #[serde(default)]
+    results: Vec<String>,
+}
+
+// With a single field FOFA returns bare strings, either "host:port" or a
+// full URL for web services
+fn result_host(result: &str) -> Option<String> {
+    let url = if result.contains("://") {
+        Url::parse(result).ok()?
+    } else {
+        Url::parse(&format!("http://{}", result)).ok()?
+    };
+    url.host_str().map(|host| host.to_lowercase())
+}
+
+// FOFA reports failures in the body with a 200 and a numeric code in
+// front of the message, e.g. "[-700] Account Invalid"
+fn body_error(errmsg: &str) -> SourceError {
+    let code = errmsg
+        .trim_start()
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+        .and_then(|(code, _)| code.trim().parse::<i64>().ok());
+    match code {
+        // Invalid account or key
+        Some(-700) => SourceError::Auth(401),
+        // Out of F points
+        Some(820031) => SourceError::RateLimited,
+        _ => SourceError::Parse(errmsg.to_string()),
+    }
+}
+
+impl FofaSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<(String, String)>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn page_url(credentials: &(String, String), domain: &str, size: usize, page: usize) -> String {
+        let (email, key) = credentials;
+        let query = STANDARD.encode(format!("domain=\"{}\"", domain));
+        let mut url = format!(
+            "{}?full=true&fields=host&page={}&size={}&key={}&qbase64={}",
+            API_URL,
+            page,
+            size,
+            key,
+            urlencoding::encode(&query)
+        );
+        if !email.is_empty() {
+            url.push_str(&format!("&email={}", urlencoding::encode(email)));
+        }
+        url
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let credentials = self.api_keys.first()?;
+        Some(Probe {
+            request: self.client.get(Self::page_url(credentials, domain, 1, 1)),
+            format: ProbeFormat::JsonField("results"),
+        })
+    }
+
+    async fn fetch_page(&self, credentials: &(String, String), domain: &str, page: usize) -> Result<FofaResponse, SourceError> {
+        let response = check_status(
+            self.client
+                .get(Self::page_url(credentials, domain, PAGE_SIZE, page))
+                .send()
+                .await?,
+        )?;
+        let data: FofaResponse = parse_json(&response.text().await?)?;
+        if data.error {
+            return Err(body_error(&data.errmsg));
+        }
+        Ok(data)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying FOFA API for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        for page in 1..=MAX_PAGES {
+            let data = match keys.with_key(|credentials| self.fetch_page(credentials, domain, page)).await {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("FOFA stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            let entries = data.results.len();
+            for host in data.results.iter().filter_map(|result| result_host(result)) {
+                if is_valid_subdomain(&host, domain) && subdomains.insert(host) {
+                    results += 1;
+                }
+            }
+
+            // `size` is the total number of matches
+            if entries < PAGE_SIZE || page * PAGE_SIZE >= data.size {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("FOFA finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_errors_follow_the_numeric_code() {
+        assert!(matches!(body_error("[-700] Account Invalid"), SourceError::Auth(401)));
+        assert!(matches!(body_error("[820031] F点余额不足"), SourceError::RateLimited));
+        // Words in the message alone say nothing about the cause
+        assert!(matches!(body_error("[820000] invalid query syntax"), SourceError::Parse(_)));
+        assert!(matches!(body_error("result limit reached"), SourceError::Parse(_)));
+    }
+}
@@ -1,26 +1,60 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
-use reqwest::Client;
+use reqwest::{Client, Response, StatusCode};
 use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, create_client, extract_subdomains, parse_json, partial_result, subdomain_pattern, KeyRing, PagePool, Probe, ProbeFormat, SourceError};
+
+const SEARCH_URL: &str = "https://api.github.com/search/code";
+const PER_PAGE: usize = 100;
+// Code search never returns more than the first 1000 results of a query
+const MAX_RESULTS: usize = 1000;
+
+/// `[github]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitHubSettings {
+    /// Extra `language:` qualified queries, each with its own 1000-result cap
+    pub languages: Vec<String>,
+    /// Extra `extension:` qualified queries
+    pub extensions: Vec<String>,
+    /// Download matched files and scan them whole instead of only the
+    /// highlighted fragments
+    pub raw_contents: bool,
+    /// Upper bound on files downloaded per run
+    pub max_raw_files: usize,
+}
+
+impl Default for GitHubSettings {
+    fn default() -> Self {
+        Self {
+            languages: Vec::new(),
+            extensions: vec!["env".to_string(), "yml".to_string(), "json".to_string()],
+            raw_contents: true,
+            max_raw_files: 200,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct GitHubSource {
     client: Arc<Client>,
     api_keys: Vec<String>,
+    pages: PagePool,
+    settings: GitHubSettings,
 }
 
 #[derive(Debug, Deserialize)]
 struct GitHubSearchResponse {
+    total_count: usize,
     items: Vec<GitHubItem>,
 }
 
 #[derive(Debug, Deserialize)]
 struct GitHubItem {
+    html_url: String,
     text_matches: Option<Vec<TextMatch>>,
 }
 
@@ -29,11 +63,51 @@ struct TextMatch {
     fragment: String,
 }
 
+fn header_u64(response: &Response, name: &str) -> Option<u64> {
+    response
+        .headers()
+        .get(name)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+// X-RateLimit-Reset is a unix timestamp
+fn reset_instant(response: &Response) -> Option<Instant> {
+    let reset = header_u64(response, "x-ratelimit-reset")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Instant::now() + Duration::from_secs(reset.saturating_sub(now) + 1))
+}
+
+/// When a 403/429 is a primary or secondary rate limit, returns the time
+/// the key may be used again
+fn rate_limited_until(response: &Response) -> Option<Instant> {
+    if !matches!(response.status(), StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS) {
+        return None;
+    }
+    if let Some(secs) = header_u64(response, "retry-after") {
+        return Some(Instant::now() + Duration::from_secs(secs));
+    }
+    if header_u64(response, "x-ratelimit-remaining") == Some(0) {
+        return Some(reset_instant(response).unwrap_or_else(|| Instant::now() + Duration::from_secs(60)));
+    }
+    None
+}
+
+// https://github.com/owner/repo/blob/<sha>/path -> https://raw.githubusercontent.com/owner/repo/<sha>/path
+fn raw_url(html_url: &str) -> Option<String> {
+    let path = html_url.strip_prefix("https://github.com/")?;
+    Some(format!("https://raw.githubusercontent.com/{}", path.replacen("/blob/", "/", 1)))
+}
+
 impl GitHubSource {
     pub fn new() -> Self {
         Self {
             client: create_client(),
             api_keys: Vec::new(),
+            pages: PagePool::default(),
+            settings: GitHubSettings::default(),
         }
     }
 
@@ -41,92 +115,182 @@ impl GitHubSource {
         self.api_keys.extend(keys);
     }
 
-    fn extract_subdomains(&self, text: &str, domain: &str) -> HashSet<String> {
-        let mut subdomains = HashSet::new();
-        
-        // Match potential subdomains using basic pattern
-        for word in text.split_whitespace() {
-            let word = word.trim_matches(|c: char| !c.is_alphanumeric() && c != '.' && c != '-')
-                .to_lowercase();
-            if !word.is_empty() && is_valid_subdomain(&word, domain) {
-                subdomains.insert(word);
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn set_page_pool(&mut self, pages: PagePool) {
+        self.pages = pages;
+    }
+
+    pub fn set_settings(&mut self, settings: GitHubSettings) {
+        self.settings = settings;
+    }
+
+    /// Search queries to run. Each one is capped at 1000 results, so
+    /// narrower variants reach files the plain query cannot.
+    fn queries(&self, domain: &str) -> Vec<String> {
+        let quoted = format!("\"{}\"", domain);
+        let mut queries = vec![format!("{} in:file", domain), quoted.clone()];
+        queries.extend(
+            self.settings
+                .languages
+                .iter()
+                .map(|language| format!("{} language:{}", quoted, language)),
+        );
+        queries.extend(
+            self.settings
+                .extensions
+                .iter()
+                .map(|extension| format!("{} extension:{}", quoted, extension)),
+        );
+        queries
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(format!("{}?q={}+in:file&per_page=1", SEARCH_URL, domain))
+                .header("Authorization", format!("token {}", api_key))
+                .header("Accept", "application/vnd.github.v3.text-match+json"),
+            format: ProbeFormat::JsonField("items"),
+        })
+    }
+
+    /// Fetches one page of a query, switching keys or waiting out the
+    /// reset whenever GitHub rate limits us
+    async fn search_page(&self, keys: &mut KeyRing<'_, String>, query: &str, page: usize) -> Result<GitHubSearchResponse, SourceError> {
+        let url = format!(
+            "{}?q={}&per_page={}&page={}",
+            SEARCH_URL,
+            urlencoding::encode(query),
+            PER_PAGE,
+            page
+        );
+
+        loop {
+            let api_key = keys.next_key().await?;
+
+            let response = self
+                .client
+                .get(&url)
+                .header("Authorization", format!("token {}", api_key))
+                .header("Accept", "application/vnd.github.v3.text-match+json")
+                .send()
+                .await?;
+
+            if let Some(until) = rate_limited_until(&response) {
+                debug!("GitHub key rate limited, rotating");
+                keys.block(api_key, until);
+                continue;
             }
+            if response.status() == StatusCode::UNAUTHORIZED {
+                warn!("GitHub rejected an API key, dropping it");
+                keys.remove(api_key, StatusCode::UNAUTHORIZED.as_u16());
+                continue;
+            }
+
+            // Rest the key before it runs dry rather than after
+            if header_u64(&response, "x-ratelimit-remaining") == Some(0) {
+                if let Some(until) = reset_instant(&response) {
+                    keys.block(api_key, until);
+                }
+            }
+
+            let response = check_status(response)?;
+            return parse_json(&response.text().await?);
         }
-        
-        subdomains
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    async fn fetch_raw(&self, url: &str) -> Result<String, SourceError> {
+        let response = check_status(self.client.get(url).send().await?)?;
+        Ok(response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         if self.api_keys.is_empty() {
-            warn!("No GitHub API keys provided, skipping...");
-            return Ok(HashSet::new());
+            return Err(SourceError::MissingKey);
         }
-
-        // Use first API key (could be randomized like virustotal if needed)
-        let api_key = &self.api_keys[0];
+        let mut keys = KeyRing::new(&self.api_keys);
 
         debug!("Querying GitHub API for domain: {}", domain);
 
-        let query = format!("{}+in:file", domain);
-        let url = format!(
-            "https://api.github.com/search/code?q={}&per_page=100",
-            query
-        );
+        let pattern = subdomain_pattern(domain);
+        let mut subdomains = HashSet::new();
+        let mut files = Vec::new();
+        let mut seen_files = HashSet::new();
+        let mut last_error = None;
 
-        let response = match self.client
-            .get(&url)
-            .header("Authorization", format!("token {}", api_key))
-            .header("Accept", "application/vnd.github.v3.text-match+json")
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("GitHub API returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
+        'queries: for query in self.queries(domain) {
+            for page in 1..=MAX_RESULTS / PER_PAGE {
+                let search = match self.search_page(&mut keys, &query, page).await {
+                    Ok(search) => search,
+                    // Every key is exhausted, later queries would fail the same way
+                    Err(e @ (SourceError::RateLimited | SourceError::Auth(_))) => {
+                        warn!("GitHub stopped at query '{}' page {}: {}", query, page, e);
+                        last_error = Some(e);
+                        break 'queries;
+                    }
+                    Err(e) => {
+                        warn!("GitHub query '{}' page {} failed: {}", query, page, e);
+                        last_error = Some(e);
+                        break;
+                    }
+                };
+
+                let item_count = search.items.len();
+                for item in search.items {
+                    for text_match in item.text_matches.into_iter().flatten() {
+                        let found = extract_subdomains(&pattern, &text_match.fragment, domain);
+                        results += found.len();
+                        subdomains.extend(found);
+                    }
+                    if seen_files.insert(item.html_url.clone()) {
+                        files.push(item.html_url);
+                    }
+                }
+
+                if item_count < PER_PAGE || page * PER_PAGE >= search.total_count {
+                    break;
                 }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query GitHub API: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        let search_results: GitHubSearchResponse = match response
-            .json()
-            .await
-        {
-            Ok(data) => data,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse GitHub API response: {}", e);
-                return Ok(HashSet::new());
             }
-        };
+        }
 
-        let mut subdomains = HashSet::new();
-        for item in search_results.items {
-            if let Some(matches) = item.text_matches {
-                for text_match in matches {
-                    let found = self.extract_subdomains(&text_match.fragment, domain);
-                    results += found.len();
-                    subdomains.extend(found);
+        if self.settings.raw_contents {
+            let raw_urls: Vec<String> = files
+                .iter()
+                .filter_map(|html_url| raw_url(html_url))
+                .take(self.settings.max_raw_files)
+                .collect();
+            debug!("Scanning {} GitHub files", raw_urls.len());
+
+            let contents = self
+                .pages
+                .fetch_all(raw_urls, |url| async move {
+                    let result = self.fetch_raw(&url).await;
+                    (url, result)
+                })
+                .await;
+
+            for (url, result) in contents {
+                match result {
+                    Ok(text) => {
+                        let found = extract_subdomains(&pattern, &text, domain);
+                        results += found.len();
+                        subdomains.extend(found);
+                    }
+                    Err(e) => debug!("Failed to fetch {}: {}", url, e),
                 }
             }
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "GitHub finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("GitHub finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
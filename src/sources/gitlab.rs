@@ -0,0 +1,284 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, extract_subdomains, parse_json, partial_result, subdomain_pattern, KeyRing, PagePool, Probe, ProbeFormat, SourceError};
+
+const PER_PAGE: usize = 100;
+
+/// `[gitlab]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitLabSettings {
+    /// Instance to search, for self-hosted GitLab
+    pub base_url: String,
+    /// Upper bound on search pages fetched
+    pub max_pages: usize,
+    /// Download matched files and scan them whole instead of only the
+    /// returned snippets
+    pub raw_contents: bool,
+    /// Upper bound on files downloaded per run
+    pub max_raw_files: usize,
+}
+
+impl Default for GitLabSettings {
+    fn default() -> Self {
+        Self {
+            base_url: "https://gitlab.com".to_string(),
+            max_pages: 10,
+            raw_contents: true,
+            max_raw_files: 200,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GitLabSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+    pages: PagePool,
+    settings: GitLabSettings,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabBlob {
+    data: String,
+    path: String,
+    #[serde(rename = "ref")]
+    git_ref: String,
+    project_id: u64,
+}
+
+impl GitLabSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+            pages: PagePool::default(),
+            settings: GitLabSettings::default(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn set_page_pool(&mut self, pages: PagePool) {
+        self.pages = pages;
+    }
+
+    pub fn set_settings(&mut self, settings: GitLabSettings) {
+        self.settings = settings;
+    }
+
+    fn api_url(&self) -> String {
+        format!("{}/api/v4", self.settings.base_url.trim_end_matches('/'))
+    }
+
+    fn search_url(&self, domain: &str, per_page: usize, page: usize) -> String {
+        format!(
+            "{}/search?scope=blobs&search={}&per_page={}&page={}",
+            self.api_url(),
+            urlencoding::encode(&format!("\"{}\"", domain)),
+            per_page,
+            page
+        )
+    }
+
+    fn raw_url(&self, blob: &GitLabBlob) -> String {
+        format!(
+            "{}/projects/{}/repository/files/{}/raw?ref={}",
+            self.api_url(),
+            blob.project_id,
+            urlencoding::encode(&blob.path),
+            urlencoding::encode(&blob.git_ref)
+        )
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(self.search_url(domain, 1, 1))
+                .header("PRIVATE-TOKEN", api_key),
+            format: ProbeFormat::JsonArray,
+        })
+    }
+
+    /// Fetches one search page and the number of the next one, if any
+    async fn search_page(&self, api_key: &str, domain: &str, page: usize) -> Result<(Vec<GitLabBlob>, Option<usize>), SourceError> {
+        let response = check_status(
+            self.client
+                .get(self.search_url(domain, PER_PAGE, page))
+                .header("PRIVATE-TOKEN", api_key)
+                .send()
+                .await?,
+        )?;
+
+        // X-Next-Page is empty on the last page
+        let next_page = response
+            .headers()
+            .get("x-next-page")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+
+        let blobs = parse_json(&response.text().await?)?;
+        Ok((blobs, next_page))
+    }
+
+    async fn fetch_raw(&self, api_key: &str, url: &str) -> Result<String, SourceError> {
+        let response = check_status(
+            self.client
+                .get(url)
+                .header("PRIVATE-TOKEN", api_key)
+                .send()
+                .await?,
+        )?;
+        Ok(response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying GitLab at {} for domain: {}", self.settings.base_url, domain);
+
+        let pattern = subdomain_pattern(domain);
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+        let mut files = Vec::new();
+        let mut seen_files = HashSet::new();
+        let mut page = 1;
+
+        loop {
+            let (blobs, next_page) = match keys.with_key(|api_key| self.search_page(api_key, domain, page)).await {
+                Ok(result) => result,
+                Err(e) => {
+                    warn!("GitLab stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            for blob in blobs {
+                let found = extract_subdomains(&pattern, &blob.data, domain);
+                results += found.len();
+                subdomains.extend(found);
+
+                let raw_url = self.raw_url(&blob);
+                if seen_files.insert(raw_url.clone()) {
+                    files.push(raw_url);
+                }
+            }
+
+            match next_page {
+                Some(next) if page < self.settings.max_pages => page = next,
+                _ => break,
+            }
+        }
+
+        if self.settings.raw_contents {
+            // Raw files go through whichever key the search left usable
+            let api_key = keys.ready().unwrap_or(&self.api_keys[0]);
+            files.truncate(self.settings.max_raw_files);
+            debug!("Scanning {} GitLab files", files.len());
+
+            let contents = self
+                .pages
+                .fetch_all(files, |url| async move {
+                    let result = self.fetch_raw(api_key, &url).await;
+                    (url, result)
+                })
+                .await;
+
+            for (url, result) in contents {
+                match result {
+                    Ok(text) => {
+                        let found = extract_subdomains(&pattern, &text, domain);
+                        results += found.len();
+                        subdomains.extend(found);
+                    }
+                    Err(e) => debug!("Failed to fetch {}: {}", url, e),
+                }
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("GitLab finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::mock::{serve, MockResponse};
+
+    const PAGE_ONE: &str = include_str!("../../tests/fixtures/gitlab/search_page1.json");
+    const PAGE_TWO: &str = include_str!("../../tests/fixtures/gitlab/search_page2.json");
+
+    /// A self-hosted instance that takes one token and serves two search pages
+    async fn instance() -> String {
+        serve(|request| {
+            if request.method != "GET" || request.header("PRIVATE-TOKEN") != Some("good-token") {
+                return MockResponse::new(401, r#"{"message":"401 Unauthorized"}"#);
+            }
+            let target = request.target.as_str();
+            if target.starts_with("/gitlab/api/v4/search") && target.ends_with("&page=1") {
+                MockResponse::new(200, PAGE_ONE).header("X-Next-Page", "2")
+            } else if target.starts_with("/gitlab/api/v4/search") && target.ends_with("&page=2") {
+                MockResponse::new(200, PAGE_TWO).header("X-Next-Page", "")
+            } else if target.starts_with("/gitlab/api/v4/projects/7/repository/files/config%2Fhosts.yml/raw") {
+                MockResponse::new(200, "upstream: raw.example.com\n")
+            } else {
+                MockResponse::new(404, "")
+            }
+        })
+        .await
+    }
+
+    fn source(base_url: String, keys: &[&str]) -> GitLabSource {
+        let mut source = GitLabSource::new();
+        source.add_api_keys(keys.iter().map(|key| key.to_string()).collect());
+        source.set_settings(GitLabSettings {
+            base_url: format!("{}/gitlab/", base_url),
+            ..GitLabSettings::default()
+        });
+        source
+    }
+
+    #[tokio::test]
+    async fn follows_next_page_on_a_self_hosted_instance() {
+        let base_url = instance().await;
+        let names = source(base_url, &["good-token"]).enumerate("example.com").await.unwrap();
+        let expected: HashSet<String> = ["www.example.com", "api.example.com", "dev.example.com", "raw.example.com"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_an_auth_error() {
+        let base_url = instance().await;
+        let result = source(base_url.clone(), &["bad-token"]).enumerate("example.com").await;
+        assert!(matches!(result, Err(SourceError::Auth(401))));
+
+        // A rejected token is dropped and the next one is used
+        let names = source(base_url, &["bad-token", "good-token"]).enumerate("example.com").await.unwrap();
+        assert!(names.contains("dev.example.com"));
+    }
+}
@@ -0,0 +1,145 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, insert_cert_names, parse_json, partial_result, Probe, ProbeFormat, SourceError};
+
+const CERTSEARCH_URL: &str = "https://transparencyreport.google.com/transparencyreport/api/v3/httpsreport/ct/certsearch";
+const MAX_PAGES: usize = 50;
+
+#[derive(Clone)]
+pub struct GoogleCTSource {
+    client: Arc<Client>,
+}
+
+/// One page of the transparency report, which answers with nested arrays
+/// instead of objects:
+/// `[["https.ct.cdsr", [certificates...], [issuers...], [prev, next, null, page, pages]]]`,
+/// where `prev` is null on the first page
+struct CertSearchPage {
+    certificates: Vec<Value>,
+    next_token: Option<String>,
+}
+
+// Every response starts with the `)]}'` XSSI guard line
+fn strip_xssi_guard(text: &str) -> &str {
+    text.strip_prefix(")]}'").unwrap_or(text).trim_start()
+}
+
+fn parse_page(text: &str) -> Result<CertSearchPage, SourceError> {
+    let data: Value = parse_json(strip_xssi_guard(text))?;
+    let report = data
+        .get(0)
+        .and_then(Value::as_array)
+        .ok_or_else(|| SourceError::Parse("unexpected certsearch layout".to_string()))?;
+
+    let certificates = report
+        .get(1)
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    // The pager carries the next token at index 1 and "page N of M" at 3 and 4
+    let pager = report.get(3).and_then(Value::as_array);
+    let next_token = pager.and_then(|pager| {
+        let token = pager.get(1)?.as_str()?;
+        let current = pager.get(3)?.as_u64()?;
+        let total = pager.get(4)?.as_u64()?;
+        (current < total).then(|| token.to_string())
+    });
+
+    Ok(CertSearchPage { certificates, next_token })
+}
+
+// Each certificate is [null, subject, issuer, not_before, not_after, hash, ...];
+// the subject is the name we want, other strings are filtered out
+fn insert_page_names(page: &CertSearchPage, domain: &str, subdomains: &mut HashSet<String>) -> usize {
+    page.certificates
+        .iter()
+        .filter_map(Value::as_array)
+        .flat_map(|cert| cert.iter().filter_map(Value::as_str))
+        .map(|name| insert_cert_names(name, domain, subdomains))
+        .sum()
+}
+
+impl GoogleCTSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+        }
+    }
+
+    fn first_page_url(domain: &str) -> String {
+        format!("{}?include_subdomains=true&include_expired=true&domain={}", CERTSEARCH_URL, domain)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(Self::first_page_url(domain)),
+            format: ProbeFormat::Text,
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        debug!("Querying Google CT for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+        let mut url = Some(Self::first_page_url(domain));
+
+        for page in 0..MAX_PAGES {
+            let Some(page_url) = url.take() else { break };
+            let parsed = match async {
+                let response = check_status(self.client.get(&page_url).send().await?)?;
+                parse_page(&response.text().await?)
+            }
+            .await
+            {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    warn!("Google CT stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            results += insert_page_names(&parsed, domain, &mut subdomains);
+
+            url = parsed
+                .next_token
+                .map(|token| format!("{}/page?p={}", CERTSEARCH_URL, urlencoding::encode(&token)));
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("Google CT finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_next_token_until_the_last_page() {
+        let first = parse_page(include_str!("../../tests/fixtures/googlect/page1.json")).unwrap();
+        assert_eq!(first.next_token.as_deref(), Some("CAESBggBEIAK"));
+        let last = parse_page(include_str!("../../tests/fixtures/googlect/page2.json")).unwrap();
+        assert_eq!(last.next_token, None);
+
+        let mut subdomains = HashSet::new();
+        insert_page_names(&first, "example.com", &mut subdomains);
+        insert_page_names(&last, "example.com", &mut subdomains);
+        let expected: HashSet<String> = ["www.example.com", "mail.example.com", "api.example.com", "dev.example.com"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(subdomains, expected);
+    }
+}
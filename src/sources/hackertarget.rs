@@ -1,11 +1,10 @@
-use anyhow::{Context, Result};
-use log::{debug, warn};
+use log::debug;
 use reqwest::Client;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, is_html_response, is_valid_subdomain, Probe, ProbeFormat, SourceError};
 
 #[derive(Clone)]
 pub struct HackerTargetSource {
@@ -22,44 +21,38 @@ impl HackerTargetSource {
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self
+                .client
+                .get(format!("https://api.hackertarget.com/hostsearch/?q={}", domain)),
+            format: ProbeFormat::Text,
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         debug!("Querying HackerTarget for domain: {}", domain);
 
         let url = format!("https://api.hackertarget.com/hostsearch/?q={}", domain);
-        let response = match self.client.get(&url).send().await {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("HackerTarget returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
-                }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query HackerTarget: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
+        let response = check_status(self.client.get(&url).send().await?)?;
+        let text = response.text().await?;
 
-        let text = match response.text().await {
-            Ok(t) => t,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to read HackerTarget response: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
+        // HackerTarget answers 200 with a plain message once the daily quota is used
+        if text.contains("API count exceeded") {
+            return Err(SourceError::RateLimited);
+        }
+        if is_html_response(&text) {
+            return Err(SourceError::UpstreamHtml);
+        }
 
         let mut subdomains = HashSet::new();
 
         // Process each line which contains subdomain,ip format
         for line in text.lines() {
-            if line.is_empty() || line.contains("API count exceeded") {
+            if line.is_empty() {
                 continue;
             }
             // Extract subdomain from the line (format: subdomain,ip)
@@ -73,10 +66,7 @@ impl HackerTargetSource {
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "HackerTarget finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
+        debug!("HackerTarget finished: {} results in {:?}", results, elapsed);
         Ok(subdomains)
     }
 }
@@ -0,0 +1,142 @@
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://hunter.qianxin.com/openApi/search";
+const PAGE_SIZE: usize = 100;
+const MAX_PAGES: usize = 20;
+
+#[derive(Clone)]
+pub struct HunterSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HunterResponse {
+    code: u16,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    data: Option<HunterData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HunterData {
+    #[serde(default)]
+    total: usize,
+    // null rather than empty when nothing matched
+    #[serde(default)]
+    arr: Option<Vec<HunterAsset>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HunterAsset {
+    #[serde(default)]
+    domain: String,
+}
+
+impl HunterSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    // The search expression goes in URL-safe base64
+    fn page_url(api_key: &str, domain: &str, size: usize, page: usize) -> String {
+        let search = URL_SAFE.encode(format!("domain.suffix=\"{}\"", domain));
+        format!(
+            "{}?api-key={}&search={}&page={}&page_size={}&is_web=3",
+            API_URL, api_key, search, page, size
+        )
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self.client.get(Self::page_url(api_key, domain, 1, 1)),
+            format: ProbeFormat::JsonField("data"),
+        })
+    }
+
+    async fn fetch_page(&self, api_key: &str, domain: &str, page: usize) -> Result<HunterData, SourceError> {
+        let response = check_status(
+            self.client
+                .get(Self::page_url(api_key, domain, PAGE_SIZE, page))
+                .send()
+                .await?,
+        )?;
+        let data: HunterResponse = parse_json(&response.text().await?)?;
+        // Hunter mirrors HTTP codes in the body and always answers 200
+        match data.code {
+            200 => Ok(data.data.unwrap_or(HunterData { total: 0, arr: None })),
+            401 => Err(SourceError::Auth(401)),
+            // 40204: out of points for today
+            429 | 40204 => Err(SourceError::RateLimited),
+            code => {
+                debug!("Hunter error {}: {}", code, data.message);
+                Err(SourceError::HttpStatus(code))
+            }
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying Hunter API for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        for page in 1..=MAX_PAGES {
+            let data = match keys.with_key(|api_key| self.fetch_page(api_key, domain, page)).await {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Hunter stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            let assets = data.arr.unwrap_or_default();
+            let entries = assets.len();
+            for asset in assets {
+                let name = asset.domain.to_lowercase();
+                if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                    results += 1;
+                }
+            }
+
+            if entries < PAGE_SIZE || page * PAGE_SIZE >= data.total {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("Hunter finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
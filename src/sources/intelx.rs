@@ -0,0 +1,160 @@
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::sources::{
+    check_status, create_client, is_valid_subdomain, parse_json, submit_and_poll, KeyRing, PollSchedule, PollStatus, Probe,
+    ProbeFormat, SourceError,
+};
+
+/// Host for keys given without one; paid plans get their own host
+pub(crate) const DEFAULT_HOST: &str = "2.intelx.io";
+const RESULT_LIMIT: usize = 10000;
+const SCHEDULE: PollSchedule = PollSchedule {
+    interval: Duration::from_secs(2),
+    max_wait: Duration::from_secs(90),
+};
+
+#[derive(Clone)]
+pub struct IntelXSource {
+    client: Arc<Client>,
+    api_keys: Vec<(String, String)>, // (host, key) pairs
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    id: String,
+    #[serde(default)]
+    status: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResultResponse {
+    #[serde(default)]
+    selectors: Option<Vec<Selector>>,
+    status: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct Selector {
+    selectorvalue: String,
+}
+
+fn search_body(domain: &str) -> serde_json::Value {
+    // target 1: domains, media 0: all media
+    serde_json::json!({
+        "term": domain,
+        "maxresults": RESULT_LIMIT,
+        "media": 0,
+        "target": 1,
+        "timeout": 20
+    })
+}
+
+impl IntelXSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<(String, String)>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    // A search costs credits, so the probe only reads the key's account info
+    pub(crate) fn probe(&self, _domain: &str) -> Option<Probe> {
+        let (host, key) = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(format!("https://{}/authenticate/info", host))
+                .header("x-key", key),
+            format: ProbeFormat::JsonField("paths"),
+        })
+    }
+
+    async fn submit(&self, credentials: &(String, String), domain: &str) -> Result<String, SourceError> {
+        let (host, key) = credentials;
+        let response = check_status(
+            self.client
+                .post(format!("https://{}/phonebook/search", host))
+                .header("x-key", key)
+                .json(&search_body(domain))
+                .send()
+                .await?,
+        )?;
+        let search: SearchResponse = parse_json(&response.text().await?)?;
+        // 1: invalid term, 2: out of search credits
+        match search.status {
+            0 => Ok(search.id),
+            2 => Err(SourceError::RateLimited),
+            status => Err(SourceError::Parse(format!("search rejected with status {}", status))),
+        }
+    }
+
+    async fn poll(&self, credentials: &(String, String), id: String) -> Result<PollStatus<Vec<String>>, SourceError> {
+        let (host, key) = credentials;
+        let response = check_status(
+            self.client
+                .get(format!("https://{}/phonebook/search/result", host))
+                .query(&[("id", id.as_str()), ("limit", &RESULT_LIMIT.to_string())])
+                .header("x-key", key)
+                .send()
+                .await?,
+        )?;
+        let result: ResultResponse = parse_json(&response.text().await?)?;
+        let names = result
+            .selectors
+            .unwrap_or_default()
+            .into_iter()
+            .map(|selector| selector.selectorvalue)
+            .collect();
+        // 0: results, more to come; 1: finished; 2: unknown id; 3: nothing yet
+        match result.status {
+            0 => Ok(PollStatus::Partial(names)),
+            1 => Ok(PollStatus::Done(names)),
+            3 => Ok(PollStatus::Pending),
+            status => Err(SourceError::Parse(format!("search {} failed with status {}", id, status))),
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying IntelX phonebook for domain: {}", domain);
+
+        // The result id only works with the key that submitted the search
+        let batches = keys
+            .with_key(|credentials| {
+                submit_and_poll(SCHEDULE, || self.submit(credentials, domain), |id| self.poll(credentials, id))
+            })
+            .await?;
+
+        let mut subdomains = HashSet::new();
+        for name in batches.into_iter().flatten() {
+            let name = name.to_lowercase();
+            if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                results += 1;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("IntelX finished: {} results in {:?}", results, elapsed);
+        Ok(subdomains)
+    }
+}
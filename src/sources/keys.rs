@@ -0,0 +1,104 @@
+use log::debug;
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::sources::SourceError;
+
+/// Longest we sleep for a key to come back before giving up on the source
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(65);
+/// How long a rate limited key rests when the upstream does not say
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// API keys of one source, rotated when one hits its rate limit and
+/// dropped when the upstream rejects them
+///
+/// A ring lives for a single enumeration, so a key that ran dry in one run
+/// is tried again in the next.
+pub(crate) struct KeyRing<'a, K> {
+    keys: Vec<(&'a K, Instant)>,
+    rejected: Option<u16>,
+}
+
+impl<'a, K: PartialEq> KeyRing<'a, K> {
+    pub fn new(keys: &'a [K]) -> Self {
+        let now = Instant::now();
+        Self {
+            keys: keys.iter().map(|key| (key, now)).collect(),
+            rejected: None,
+        }
+    }
+
+    /// A key that is not resting right now
+    pub fn ready(&self) -> Option<&'a K> {
+        let now = Instant::now();
+        self.keys
+            .iter()
+            .find(|(_, until)| *until <= now)
+            .map(|(key, _)| *key)
+    }
+
+    /// Rests a key until the given time
+    pub fn block(&mut self, key: &K, until: Instant) {
+        if let Some(entry) = self.keys.iter_mut().find(|(k, _)| *k == key) {
+            entry.1 = until;
+        }
+    }
+
+    /// Drops a key the upstream rejected with `status`
+    pub fn remove(&mut self, key: &K, status: u16) {
+        self.keys.retain(|(k, _)| *k != key);
+        self.rejected = Some(status);
+    }
+
+    // Why no key is ready: all were rejected, or all are resting
+    fn exhausted(&self) -> SourceError {
+        if self.keys.is_empty() {
+            SourceError::Auth(self.rejected.unwrap_or(StatusCode::UNAUTHORIZED.as_u16()))
+        } else {
+            SourceError::RateLimited
+        }
+    }
+
+    /// A usable key, sleeping until one comes back when every key is
+    /// resting for a short while
+    pub async fn next_key(&self) -> Result<&'a K, SourceError> {
+        loop {
+            if let Some(key) = self.ready() {
+                return Ok(key);
+            }
+            let now = Instant::now();
+            match self.keys.iter().map(|(_, until)| until.saturating_duration_since(now)).min() {
+                Some(wait) if wait <= MAX_RATE_LIMIT_WAIT => {
+                    debug!("All keys are rate limited, waiting {:?}", wait);
+                    tokio::time::sleep(wait).await;
+                }
+                _ => return Err(self.exhausted()),
+            }
+        }
+    }
+
+    /// Runs `request` with the first ready key, moving on to the next key
+    /// when it is rate limited or rejected. Fails without waiting once no
+    /// key is left to try.
+    pub async fn with_key<T, F, Fut>(&mut self, mut request: F) -> Result<T, SourceError>
+    where
+        F: FnMut(&'a K) -> Fut,
+        Fut: Future<Output = Result<T, SourceError>>,
+    {
+        loop {
+            let key = self.ready().ok_or_else(|| self.exhausted())?;
+            match request(key).await {
+                Err(SourceError::RateLimited) => {
+                    debug!("Key rate limited, rotating");
+                    self.block(key, Instant::now() + DEFAULT_COOLDOWN);
+                }
+                Err(SourceError::Auth(status)) => {
+                    debug!("Key rejected (HTTP {}), rotating", status);
+                    self.remove(key, status);
+                }
+                result => return result,
+            }
+        }
+    }
+}
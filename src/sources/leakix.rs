@@ -0,0 +1,91 @@
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, KeyRing, Probe, ProbeFormat, SourceError};
+
+#[derive(Clone)]
+pub struct LeakIXSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeakIXSubdomain {
+    subdomain: String,
+}
+
+impl LeakIXSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn url(domain: &str) -> String {
+        format!("https://leakix.net/api/subdomains/{}", domain)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(Self::url(domain))
+                .header("api-key", api_key)
+                .header("Accept", "application/json"),
+            format: ProbeFormat::JsonArray,
+        })
+    }
+
+    async fn fetch(&self, api_key: &str, domain: &str) -> Result<Vec<LeakIXSubdomain>, SourceError> {
+        let response = check_status(
+            self.client
+                .get(Self::url(domain))
+                .header("api-key", api_key)
+                .header("Accept", "application/json")
+                .send()
+                .await?,
+        )?;
+        parse_json(&response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying LeakIX API for domain: {}", domain);
+
+        let entries = keys.with_key(|api_key| self.fetch(api_key, domain)).await?;
+
+        let mut subdomains = HashSet::new();
+        for entry in entries {
+            let subdomain = entry.subdomain.to_lowercase();
+            if is_valid_subdomain(&subdomain, domain) {
+                results += 1;
+                subdomains.insert(subdomain);
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("LeakIX finished: {} results in {:?}", results, elapsed);
+        Ok(subdomains)
+    }
+}
@@ -0,0 +1,367 @@
+use log::{debug, warn};
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::sources::{is_valid_subdomain, partial_result, SourceError};
+
+/// How a dataset file is laid out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetFormat {
+    /// One name per line
+    #[default]
+    Lines,
+    /// One JSON object per line, name at `field`
+    Jsonl,
+    /// Zeek `dns.log` in its tab separated form
+    Zeek,
+    /// A SQLite table, names in `column`
+    Sqlite,
+}
+
+/// One `[[local.datasets]]` entry
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LocalDataset {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: DatasetFormat,
+    /// JSON pointer to the name in each JSONL record
+    #[serde(default = "default_field")]
+    pub field: String,
+    /// SQLite table and column holding the names
+    #[serde(default)]
+    pub table: Option<String>,
+    #[serde(default = "default_column")]
+    pub column: String,
+}
+
+fn default_field() -> String {
+    "/host".to_string()
+}
+
+fn default_column() -> String {
+    "name".to_string()
+}
+
+/// `[local]` section of the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalSettings {
+    pub datasets: Vec<LocalDataset>,
+}
+
+impl LocalSettings {
+    /// Catches datasets that could never be read before a run starts
+    pub fn validate(&self) -> Result<(), String> {
+        for dataset in &self.datasets {
+            if dataset.format == DatasetFormat::Sqlite && dataset.table.is_none() {
+                return Err(format!("{}: sqlite datasets need a `table`", dataset.path.display()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every name of every dataset, stored with its labels reversed
+/// (`www.example.com` as `com.example.www`) so the names under a domain
+/// form one contiguous range
+type Index = BTreeSet<String>;
+
+#[derive(Clone)]
+pub struct LocalSource {
+    settings: LocalSettings,
+    // Built on first use and shared by every enumeration of the run
+    index: Arc<OnceLock<Result<Index, SourceError>>>,
+}
+
+fn reverse_labels(name: &str) -> String {
+    name.split('.').rev().collect::<Vec<_>>().join(".")
+}
+
+// Datasets hold whatever was captured: trailing dots, wildcards, mixed case
+fn normalize(name: &str) -> Option<String> {
+    let name = name
+        .trim()
+        .trim_end_matches('.')
+        .trim_start_matches("*.")
+        .to_lowercase();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn read_lines(path: &Path, mut insert: impl FnMut(&str)) -> Result<(), SourceError> {
+    for line in BufReader::new(File::open(path)?).lines() {
+        insert(&line?);
+    }
+    Ok(())
+}
+
+fn read_jsonl(path: &Path, field: &str, mut insert: impl FnMut(&str)) -> Result<(), SourceError> {
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Value = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(e) => {
+                debug!("Skipping bad JSONL record in {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if let Some(name) = record.pointer(field).and_then(|v| v.as_str()) {
+            insert(name);
+        }
+    }
+    Ok(())
+}
+
+// Zeek names its columns in a `#fields` header; queries and the CNAME
+// targets among the answers are both worth keeping
+fn read_zeek(path: &Path, mut insert: impl FnMut(&str)) -> Result<(), SourceError> {
+    let mut query = None;
+    let mut answers = None;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if let Some(fields) = line.strip_prefix("#fields\t") {
+            let fields: Vec<&str> = fields.split('\t').collect();
+            query = fields.iter().position(|f| *f == "query");
+            answers = fields.iter().position(|f| *f == "answers");
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let columns: Vec<&str> = line.split('\t').collect();
+        if let Some(name) = query.and_then(|i| columns.get(i)) {
+            insert(name);
+        }
+        if let Some(list) = answers.and_then(|i| columns.get(i)) {
+            list.split(',').for_each(&mut insert);
+        }
+    }
+    if query.is_none() {
+        return Err(SourceError::Parse(format!("{} has no #fields header with a query column", path.display())));
+    }
+    Ok(())
+}
+
+fn read_sqlite(path: &Path, table: &str, column: &str, mut insert: impl FnMut(&str)) -> Result<(), SourceError> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let quote = |identifier: &str| format!("\"{}\"", identifier.replace('"', "\"\""));
+    let mut statement = connection.prepare(&format!("SELECT {} FROM {}", quote(column), quote(table)))?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        if let Some(name) = row.get::<_, Option<String>>(0)? {
+            insert(&name);
+        }
+    }
+    Ok(())
+}
+
+fn build_index(datasets: &[LocalDataset]) -> Result<Index, SourceError> {
+    let mut index = Index::new();
+    let mut last_error = None;
+
+    for dataset in datasets {
+        let before = index.len();
+        let mut insert = |name: &str| {
+            if let Some(name) = normalize(name) {
+                index.insert(reverse_labels(&name));
+            }
+        };
+        let path = &dataset.path;
+        let read = match dataset.format {
+            DatasetFormat::Lines => read_lines(path, &mut insert),
+            DatasetFormat::Jsonl => read_jsonl(path, &dataset.field, &mut insert),
+            DatasetFormat::Zeek => read_zeek(path, &mut insert),
+            DatasetFormat::Sqlite => {
+                let table = dataset.table.as_deref().unwrap_or_default();
+                read_sqlite(path, table, &dataset.column, &mut insert)
+            }
+        };
+        match read {
+            Ok(()) => debug!("Loaded {} names from {}", index.len() - before, path.display()),
+            Err(e) => {
+                warn!("Skipping dataset {}: {}", path.display(), e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    partial_result(index, last_error)
+}
+
+impl LocalSource {
+    pub fn new() -> Self {
+        Self {
+            settings: LocalSettings::default(),
+            index: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: LocalSettings) {
+        self.settings = settings;
+        self.index = Arc::new(OnceLock::new());
+    }
+
+    fn lookup(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let index = self
+            .index
+            .get_or_init(|| build_index(&self.settings.datasets))
+            .as_ref()
+            .map_err(Clone::clone)?;
+
+        let prefix = format!("{}.", reverse_labels(domain));
+        Ok(index
+            .range(prefix.clone()..)
+            .take_while(|name| name.starts_with(&prefix))
+            .map(|name| reverse_labels(name))
+            .filter(|name| is_valid_subdomain(name, domain))
+            .collect())
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+
+        if self.settings.datasets.is_empty() {
+            debug!("No local datasets configured");
+            return Ok(HashSet::new());
+        }
+
+        debug!("Searching {} local datasets for domain: {}", self.settings.datasets.len(), domain);
+
+        // Reading the datasets is blocking file and SQLite work
+        let source = self.clone();
+        let target = domain.to_string();
+        let subdomains = tokio::task::spawn_blocking(move || source.lookup(&target))
+            .await
+            .map_err(|e| SourceError::Io(e.to_string()))??;
+
+        let elapsed = start_time.elapsed();
+        debug!("Local finished: {} results in {:?}", subdomains.len(), elapsed);
+        Ok(subdomains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/local").join(name)
+    }
+
+    // Builds the SQLite dataset from its SQL fixture in a throwaway file
+    fn sqlite_fixture(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("subfinder-local-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(include_str!("../../tests/fixtures/local/hosts.sql"))
+            .unwrap();
+        path
+    }
+
+    fn read(reader: impl FnOnce(&mut dyn FnMut(&str)) -> Result<(), SourceError>) -> Vec<String> {
+        let mut names = Vec::new();
+        reader(&mut |name| names.push(name.to_string())).unwrap();
+        names
+    }
+
+    fn dataset(path: PathBuf, format: DatasetFormat) -> LocalDataset {
+        LocalDataset {
+            path,
+            format,
+            field: default_field(),
+            table: None,
+            column: default_column(),
+        }
+    }
+
+    #[test]
+    fn zeek_reads_queries_and_split_answers() {
+        let names = read(|insert| read_zeek(&fixture("dns.log"), insert));
+        assert_eq!(
+            names,
+            vec![
+                "mail.example.com",
+                "192.0.2.25",
+                "assets.example.com",
+                "cdn.example.com",
+                "192.0.2.30",
+                "shop.notexample.com",
+                "-"
+            ]
+        );
+
+        // Without the header there is no telling which column is the query
+        let headerless = read_zeek(&fixture("hosts.txt"), |_| {});
+        assert!(matches!(headerless, Err(SourceError::Parse(_))));
+    }
+
+    #[test]
+    fn jsonl_reads_the_field_and_skips_bad_records() {
+        let names = read(|insert| read_jsonl(&fixture("hosts.jsonl"), "/host", insert));
+        assert_eq!(names, vec!["api.example.com", "deep.sub.example.com", "example.com.evil.net"]);
+    }
+
+    #[test]
+    fn sqlite_reads_the_column() {
+        let path = sqlite_fixture("read");
+        let names = read(|insert| read_sqlite(&path, "hosts", "name", insert));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(names, vec!["vpn.example.com", "Intranet.Example.com.", "example.community"]);
+    }
+
+    #[test]
+    fn lookup_returns_only_names_under_the_domain() {
+        let path = sqlite_fixture("lookup");
+        let mut source = LocalSource::new();
+        source.set_settings(LocalSettings {
+            datasets: vec![
+                dataset(fixture("hosts.txt"), DatasetFormat::Lines),
+                dataset(fixture("hosts.jsonl"), DatasetFormat::Jsonl),
+                dataset(fixture("dns.log"), DatasetFormat::Zeek),
+                LocalDataset {
+                    table: Some("hosts".to_string()),
+                    ..dataset(path.clone(), DatasetFormat::Sqlite)
+                },
+            ],
+        });
+
+        let names = source.lookup("example.com");
+        let siblings = source.lookup("notexample.com");
+        let _ = std::fs::remove_file(&path);
+
+        let expected: HashSet<String> = [
+            "www.example.com",
+            "wild.example.com",
+            "api.example.com",
+            "deep.sub.example.com",
+            "mail.example.com",
+            "assets.example.com",
+            "cdn.example.com",
+            "vpn.example.com",
+            "intranet.example.com",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(names.unwrap(), expected);
+
+        let expected: HashSet<String> = ["www.notexample.com", "shop.notexample.com"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(siblings.unwrap(), expected);
+    }
+}
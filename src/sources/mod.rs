@@ -1,47 +1,123 @@
-use anyhow::Result;
-use reqwest::Client;
+use futures::StreamExt;
+use regex::Regex;
+use reqwest::{Client, ClientBuilder, Response};
 use serde_json::Value;
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
+use crate::settings::Settings;
+
 // Module declarations
 mod alienvault;
 mod anubis;
+mod binaryedge;
 mod bufferover;
+mod c99;
 mod censys;
 mod certspotter;
 mod chaos;
 mod commoncrawl;
 mod crtsh;
+mod declarative;
 mod dnsdb;
 mod dnsdumpster;
+mod error;
+mod facebook;
+mod fofa;
+mod fullhunt;
 mod github;
+mod gitlab;
+mod googlect;
 mod hackertarget;
+mod hunter;
+mod intelx;
+mod keys;
+mod leakix;
+mod local;
+#[cfg(test)]
+mod mock;
+mod netlas;
+mod plugin;
+mod poll;
+mod pool;
+mod quake;
 mod rapiddns;
 mod riddler;
+mod robtex;
+mod searchengine;
+mod securitytrails;
+mod shodan;
+mod sitedossier;
 mod threatcrowd;
+mod threatminer;
+mod urlscan;
 mod virustotal;
 mod webarchive;
+mod whoisxmlapi;
+mod zoomeye;
 
 // Use declarations
 use self::alienvault::AlienVaultSource;
 use self::anubis::AnubisSource;
+use self::binaryedge::BinaryEdgeSource;
 use self::bufferover::BufferOverSource;
+use self::c99::C99Source;
 use self::censys::CensysSource;
+pub use self::censys::CensysSettings;
 use self::certspotter::CertSpotterSource;
 use self::chaos::ChaosSource;
 use self::commoncrawl::CommonCrawlSource;
+pub use self::commoncrawl::CommonCrawlSettings;
 use self::crtsh::CrtShSource;
+pub use self::crtsh::CrtShSettings;
+use self::declarative::DeclarativeSource;
+pub use self::declarative::DeclarativeSettings;
 use self::dnsdb::DNSDBSource;
+pub use self::dnsdb::DNSDBSettings;
 use self::dnsdumpster::DNSDumpsterSource;
+pub use self::error::SourceError;
+pub(crate) use self::error::{check_status, parse_json};
+pub(crate) use self::keys::KeyRing;
+pub(crate) use self::poll::{submit_and_poll, PollSchedule, PollStatus};
+pub use self::pool::{PagePool, DEFAULT_PAGE_CONCURRENCY};
+use self::facebook::FacebookSource;
+use self::fofa::FofaSource;
+use self::fullhunt::FullHuntSource;
 use self::github::GitHubSource;
+pub use self::github::GitHubSettings;
+use self::gitlab::GitLabSource;
+pub use self::gitlab::GitLabSettings;
+use self::googlect::GoogleCTSource;
 use self::hackertarget::HackerTargetSource;
+use self::hunter::HunterSource;
+use self::intelx::IntelXSource;
+use self::leakix::LeakIXSource;
+use self::local::LocalSource;
+pub use self::local::LocalSettings;
+use self::netlas::NetlasSource;
+pub use self::netlas::NetlasSettings;
+use self::plugin::PluginSource;
+pub use self::plugin::PluginSettings;
+use self::quake::QuakeSource;
 use self::rapiddns::RapidDNSSource;
 use self::riddler::RiddlerSource;
+use self::searchengine::{SearchEngine, SearchEngineSource};
+pub use self::searchengine::SearchEngineSettings;
+use self::robtex::RobtexSource;
+use self::securitytrails::SecurityTrailsSource;
+pub use self::securitytrails::SecurityTrailsSettings;
+use self::shodan::ShodanSource;
+pub use self::shodan::ShodanSettings;
+use self::sitedossier::SiteDossierSource;
 use self::threatcrowd::ThreatCrowdSource;
+use self::threatminer::ThreatMinerSource;
+use self::urlscan::URLScanSource;
 use self::virustotal::VirusTotalSource;
 use self::webarchive::WebArchiveSource;
+pub use self::webarchive::WebArchiveSettings;
+use self::whoisxmlapi::WhoisXMLAPISource;
+use self::zoomeye::ZoomEyeSource;
 
 /// Creates a new HTTP client with optimized settings
 pub(crate) fn create_client() -> Arc<Client> {
@@ -50,7 +126,22 @@ pub(crate) fn create_client() -> Arc<Client> {
 
 /// Creates a new HTTP client with proxy support
 pub(crate) fn create_client_with_proxy(proxy: Option<String>) -> Arc<Client> {
-    let mut builder = Client::builder()
+    let mut builder = client_builder();
+
+    // Add proxy if configured
+    if let Some(proxy_url) = proxy {
+        if let Ok(proxy) = reqwest::Proxy::all(&proxy_url) {
+            builder = builder.proxy(proxy);
+        }
+    }
+
+    Arc::new(builder.build().expect("Failed to build HTTP client"))
+}
+
+/// The settings every client starts from, for sources that need to add
+/// their own on top
+pub(crate) fn client_builder() -> ClientBuilder {
+    Client::builder()
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")  // Use a more common user agent
         .timeout(Duration::from_secs(60))  // Increase timeout
         .pool_idle_timeout(Duration::from_secs(90))
@@ -60,21 +151,104 @@ pub(crate) fn create_client_with_proxy(proxy: Option<String>) -> Arc<Client> {
         .danger_accept_invalid_certs(true)  // Accept invalid certificates
         .http1_only()  // Disable HTTP/2 to avoid frame size issues
         .connect_timeout(Duration::from_secs(30))  // Increase connect timeout
-        .local_address(Some(std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0))));  // Allow any local interface
+        .local_address(Some(std::net::IpAddr::V4(std::net::Ipv4Addr::new(0, 0, 0, 0))))  // Allow any local interface
+}
 
-    // Add proxy if configured
-    if let Some(proxy_url) = proxy {
-        if let Ok(proxy) = reqwest::Proxy::all(&proxy_url) {
-            builder = builder.proxy(proxy);
+// Helper function to check if a response is HTML
+pub(crate) fn is_html_response(text: &str) -> bool {
+    text.contains("<html") || text.contains("<!DOCTYPE")
+}
+
+// Helper function to stream a line-oriented body without buffering all of it.
+// Large CDX and NDJSON responses can run into gigabytes.
+pub(crate) async fn for_each_line<F: FnMut(&str)>(response: Response, mut f: F) -> Result<(), SourceError> {
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut first_chunk = true;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if first_chunk {
+            first_chunk = false;
+            if is_html_response(&String::from_utf8_lossy(&chunk)) {
+                return Err(SourceError::UpstreamHtml);
+            }
+        }
+
+        buffer.extend_from_slice(&chunk);
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            f(String::from_utf8_lossy(&line).trim_end());
         }
     }
 
-    Arc::new(builder.build().expect("Failed to build HTTP client"))
+    if !buffer.is_empty() {
+        f(String::from_utf8_lossy(&buffer).trim_end());
+    }
+    Ok(())
 }
 
-// Helper function to check if a response is HTML
-pub(crate) fn is_html_response(text: &str) -> bool {
-    text.contains("<html") || text.contains("<!DOCTYPE")
+// Helper function to read a key entry given either as one string or as a
+// list of strings, for sources that rotate between keys
+pub(crate) fn string_keys(api_keys: &Value, name: &str) -> Vec<String> {
+    match api_keys.get(name) {
+        Some(Value::String(key)) => vec![key.clone()],
+        Some(Value::Array(keys)) => keys
+            .iter()
+            .filter_map(|key| key.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Helper function to build a pattern matching names under `domain` in free
+// text such as source code
+pub(crate) fn subdomain_pattern(domain: &str) -> Regex {
+    Regex::new(&format!(
+        r"(?i)(?:[a-z0-9](?:[a-z0-9-]{{0,61}}[a-z0-9])?\.)+{}",
+        regex::escape(domain)
+    ))
+    .expect("Invalid subdomain pattern")
+}
+
+// Helper function to collect the valid subdomains `pattern` finds in `text`
+pub(crate) fn extract_subdomains(pattern: &Regex, text: &str, domain: &str) -> HashSet<String> {
+    pattern
+        .find_iter(text)
+        .map(|m| m.as_str().to_lowercase())
+        .filter(|name| is_valid_subdomain(name, domain))
+        .collect()
+}
+
+// Helper function to settle a source that failed part way through. Names
+// found before a later page, query or endpoint broke are still worth
+// reporting, so the error only surfaces when nothing was found at all.
+pub(crate) fn partial_result<T>(found: T, last_error: Option<SourceError>) -> Result<T, SourceError>
+where
+    for<'a> &'a T: IntoIterator,
+{
+    match last_error {
+        Some(e) if (&found).into_iter().next().is_none() => Err(e),
+        _ => Ok(found),
+    }
+}
+
+// Helper function to add the names from a certificate field. Fields such as
+// crt.sh's name_value hold several names separated by newlines, and
+// wildcard names are reduced to the name they cover.
+pub(crate) fn insert_cert_names(names: &str, domain: &str, subdomains: &mut HashSet<String>) -> usize {
+    let mut count = 0;
+    for name in names.split('\n') {
+        let name = name.trim()
+            .trim_start_matches("*.")
+            .trim_start_matches('.')
+            .to_lowercase();
+
+        if !name.is_empty() && is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+            count += 1;
+        }
+    }
+    count
 }
 
 // Helper function to validate a subdomain
@@ -131,12 +305,34 @@ pub(crate) fn is_valid_subdomain(subdomain: &str, domain: &str) -> bool {
     true
 }
 
+/// Response shape a source's health-check probe is expected to return
+pub(crate) enum ProbeFormat {
+    /// Top-level JSON array
+    JsonArray,
+    /// Any JSON document
+    Json,
+    /// JSON object containing the given field
+    JsonField(&'static str),
+    /// Plain text or CSV
+    Text,
+    /// Scraped HTML page
+    Html,
+}
+
+/// Minimal request used by `subfinder-rs check` to test a source
+pub(crate) struct Probe {
+    pub request: reqwest::RequestBuilder,
+    pub format: ProbeFormat,
+}
+
 #[derive(Clone)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum SourceType {
     CrtSh(CrtShSource),
     WebArchive(WebArchiveSource),
     Chaos(ChaosSource),
     GitHub(GitHubSource),
+    GitLab(GitLabSource),
     DNSDB(DNSDBSource),
     Censys(CensysSource),
     AlienVault(AlienVaultSource),
@@ -150,6 +346,32 @@ pub enum SourceType {
     DNSDumpster(DNSDumpsterSource),
     CommonCrawl(CommonCrawlSource),
     Riddler(RiddlerSource),
+    Shodan(ShodanSource),
+    SecurityTrails(SecurityTrailsSource),
+    URLScan(URLScanSource),
+    Bing(SearchEngineSource),
+    DuckDuckGo(SearchEngineSource),
+    Yahoo(SearchEngineSource),
+    Baidu(SearchEngineSource),
+    Facebook(FacebookSource),
+    GoogleCT(GoogleCTSource),
+    Robtex(RobtexSource),
+    ThreatMiner(ThreatMinerSource),
+    SiteDossier(SiteDossierSource),
+    BinaryEdge(BinaryEdgeSource),
+    FullHunt(FullHuntSource),
+    Netlas(NetlasSource),
+    LeakIX(LeakIXSource),
+    ZoomEye(ZoomEyeSource),
+    Quake(QuakeSource),
+    Hunter(HunterSource),
+    Fofa(FofaSource),
+    WhoisXMLAPI(WhoisXMLAPISource),
+    C99(C99Source),
+    IntelX(IntelXSource),
+    Declarative(DeclarativeSource),
+    Plugin(PluginSource),
+    Local(LocalSource),
 }
 
 impl SourceType {
@@ -159,6 +381,7 @@ impl SourceType {
             SourceType::WebArchive(_) => "webarchive".to_string(),
             SourceType::Chaos(_) => "chaos".to_string(),
             SourceType::GitHub(_) => "github".to_string(),
+            SourceType::GitLab(_) => "gitlab".to_string(),
             SourceType::DNSDB(_) => "dnsdb".to_string(),
             SourceType::Censys(_) => "censys".to_string(),
             SourceType::AlienVault(_) => "alienvault".to_string(),
@@ -172,15 +395,318 @@ impl SourceType {
             SourceType::DNSDumpster(_) => "dnsdumpster".to_string(),
             SourceType::CommonCrawl(_) => "commoncrawl".to_string(),
             SourceType::Riddler(_) => "riddler".to_string(),
+            SourceType::Shodan(_) => "shodan".to_string(),
+            SourceType::SecurityTrails(_) => "securitytrails".to_string(),
+            SourceType::URLScan(_) => "urlscan".to_string(),
+            SourceType::Bing(_) => "bing".to_string(),
+            SourceType::DuckDuckGo(_) => "duckduckgo".to_string(),
+            SourceType::Yahoo(_) => "yahoo".to_string(),
+            SourceType::Baidu(_) => "baidu".to_string(),
+            SourceType::Facebook(_) => "facebook".to_string(),
+            SourceType::GoogleCT(_) => "googlect".to_string(),
+            SourceType::Robtex(_) => "robtex".to_string(),
+            SourceType::ThreatMiner(_) => "threatminer".to_string(),
+            SourceType::SiteDossier(_) => "sitedossier".to_string(),
+            SourceType::BinaryEdge(_) => "binaryedge".to_string(),
+            SourceType::FullHunt(_) => "fullhunt".to_string(),
+            SourceType::Netlas(_) => "netlas".to_string(),
+            SourceType::LeakIX(_) => "leakix".to_string(),
+            SourceType::ZoomEye(_) => "zoomeye".to_string(),
+            SourceType::Quake(_) => "quake".to_string(),
+            SourceType::Hunter(_) => "hunter".to_string(),
+            SourceType::Fofa(_) => "fofa".to_string(),
+            SourceType::WhoisXMLAPI(_) => "whoisxmlapi".to_string(),
+            SourceType::C99(_) => "c99".to_string(),
+            SourceType::IntelX(_) => "intelx".to_string(),
+            SourceType::Declarative(source) => source.name().to_string(),
+            SourceType::Plugin(source) => source.name().to_string(),
+            SourceType::Local(_) => "local".to_string(),
+        }
+    }
+
+    /// Whether the source returns nothing without an API key
+    pub fn requires_key(&self) -> bool {
+        matches!(
+            self,
+            SourceType::Chaos(_)
+                | SourceType::GitHub(_)
+                | SourceType::GitLab(_)
+                | SourceType::DNSDB(_)
+                | SourceType::Censys(_)
+                | SourceType::CertSpotter(_)
+                | SourceType::VirusTotal(_)
+                | SourceType::Shodan(_)
+                | SourceType::SecurityTrails(_)
+                | SourceType::Facebook(_)
+                | SourceType::BinaryEdge(_)
+                | SourceType::FullHunt(_)
+                | SourceType::Netlas(_)
+                | SourceType::LeakIX(_)
+                | SourceType::ZoomEye(_)
+                | SourceType::Quake(_)
+                | SourceType::Hunter(_)
+                | SourceType::Fofa(_)
+                | SourceType::WhoisXMLAPI(_)
+                | SourceType::C99(_)
+                | SourceType::IntelX(_)
+        ) || matches!(self, SourceType::Declarative(source) if source.requires_key())
+            || matches!(self, SourceType::Plugin(source) if source.requires_key())
+    }
+
+    /// Whether at least one API key has been loaded for the source
+    pub fn has_key(&self) -> bool {
+        match self {
+            SourceType::Chaos(source) => source.has_api_keys(),
+            SourceType::GitHub(source) => source.has_api_keys(),
+            SourceType::GitLab(source) => source.has_api_keys(),
+            SourceType::DNSDB(source) => source.has_api_keys(),
+            SourceType::Censys(source) => source.has_api_keys(),
+            SourceType::CertSpotter(source) => source.has_api_keys(),
+            SourceType::VirusTotal(source) => source.has_api_keys(),
+            SourceType::AlienVault(source) => source.has_api_keys(),
+            SourceType::Shodan(source) => source.has_api_keys(),
+            SourceType::SecurityTrails(source) => source.has_api_keys(),
+            SourceType::URLScan(source) => source.has_api_keys(),
+            SourceType::Facebook(source) => source.has_api_keys(),
+            SourceType::BinaryEdge(source) => source.has_api_keys(),
+            SourceType::FullHunt(source) => source.has_api_keys(),
+            SourceType::Netlas(source) => source.has_api_keys(),
+            SourceType::LeakIX(source) => source.has_api_keys(),
+            SourceType::ZoomEye(source) => source.has_api_keys(),
+            SourceType::Quake(source) => source.has_api_keys(),
+            SourceType::Hunter(source) => source.has_api_keys(),
+            SourceType::Fofa(source) => source.has_api_keys(),
+            SourceType::WhoisXMLAPI(source) => source.has_api_keys(),
+            SourceType::C99(source) => source.has_api_keys(),
+            SourceType::IntelX(source) => source.has_api_keys(),
+            SourceType::Declarative(source) => source.has_api_keys(),
+            SourceType::Plugin(source) => source.has_api_keys(),
+            _ => false,
+        }
+    }
+
+    /// Whether the source also returns names below a queried subdomain
+    pub fn supports_recursive(&self) -> bool {
+        matches!(
+            self,
+            SourceType::AlienVault(_)
+                | SourceType::Anubis(_)
+                | SourceType::BufferOver(_)
+                | SourceType::CertSpotter(_)
+                | SourceType::CrtSh(_)
+                | SourceType::DNSDB(_)
+                | SourceType::HackerTarget(_)
+                | SourceType::VirusTotal(_)
+                | SourceType::SecurityTrails(_)
+                | SourceType::Facebook(_)
+                | SourceType::GoogleCT(_)
+                | SourceType::ThreatMiner(_)
+                | SourceType::Local(_)
+        ) || matches!(self, SourceType::Declarative(source) if source.supports_recursive())
+            || matches!(self, SourceType::Plugin(source) if source.supports_recursive())
+    }
+
+    /// Whether the source runs without `--all`. Dead and very slow
+    /// sources are opt-in.
+    pub fn is_default(&self) -> bool {
+        !matches!(
+            self,
+            SourceType::BufferOver(_)
+                | SourceType::ThreatCrowd(_)
+                | SourceType::CommonCrawl(_)
+                | SourceType::Bing(_)
+                | SourceType::DuckDuckGo(_)
+                | SourceType::Yahoo(_)
+                | SourceType::Baidu(_)
+        ) && !matches!(self, SourceType::Declarative(source) if !source.is_default())
+            && !matches!(self, SourceType::Plugin(source) if !source.is_default())
+    }
+
+    /// Whether the source answers with a single request, used by `--fast`
+    pub fn is_fast(&self) -> bool {
+        !matches!(
+            self,
+            SourceType::BufferOver(_)
+                | SourceType::ThreatCrowd(_)
+                | SourceType::CommonCrawl(_)
+                | SourceType::RapidDNS(_)
+                | SourceType::DNSDumpster(_)
+                | SourceType::CertSpotter(_)
+                | SourceType::VirusTotal(_)
+                | SourceType::GitHub(_)
+                | SourceType::GitLab(_)
+                | SourceType::Censys(_)
+                | SourceType::AlienVault(_)
+                | SourceType::Shodan(_)
+                | SourceType::SecurityTrails(_)
+                | SourceType::URLScan(_)
+                | SourceType::Bing(_)
+                | SourceType::DuckDuckGo(_)
+                | SourceType::Yahoo(_)
+                | SourceType::Baidu(_)
+                | SourceType::Facebook(_)
+                | SourceType::GoogleCT(_)
+                | SourceType::Robtex(_)
+                | SourceType::SiteDossier(_)
+                | SourceType::BinaryEdge(_)
+                | SourceType::Netlas(_)
+                | SourceType::ZoomEye(_)
+                | SourceType::Quake(_)
+                | SourceType::Hunter(_)
+                | SourceType::Fofa(_)
+                | SourceType::IntelX(_)
+                | SourceType::Plugin(_)
+        ) && !matches!(self, SourceType::Declarative(source) if !source.is_fast())
+    }
+
+    /// Scheduling priority when no override is configured. High-yield
+    /// sources go first so they are not stuck behind slow ones.
+    pub fn default_priority(&self) -> i32 {
+        match self {
+            SourceType::CrtSh(_)
+            | SourceType::VirusTotal(_)
+            | SourceType::Chaos(_)
+            | SourceType::CertSpotter(_)
+            | SourceType::AlienVault(_)
+            | SourceType::HackerTarget(_)
+            | SourceType::Anubis(_) => 10,
+            SourceType::DNSDumpster(_)
+            | SourceType::BufferOver(_)
+            | SourceType::ThreatCrowd(_)
+            | SourceType::CommonCrawl(_)
+            | SourceType::Bing(_)
+            | SourceType::DuckDuckGo(_)
+            | SourceType::Yahoo(_)
+            | SourceType::Baidu(_) => 0,
+            _ => 5,
+        }
+    }
+
+    /// Time budget for sources that page through many requests; the
+    /// global timeout applies when it is longer
+    pub fn default_timeout(&self) -> Option<Duration> {
+        match self {
+            SourceType::CommonCrawl(_) => Some(Duration::from_secs(180)),
+            SourceType::WebArchive(_) => Some(Duration::from_secs(180)),
+            // Room for the JSON API and then the SQL fallback
+            SourceType::CrtSh(_) => Some(Duration::from_secs(90)),
+            SourceType::GitHub(_) => Some(Duration::from_secs(180)),
+            SourceType::GitLab(_) => Some(Duration::from_secs(120)),
+            SourceType::RapidDNS(_) | SourceType::CertSpotter(_) | SourceType::VirusTotal(_) => {
+                Some(Duration::from_secs(90))
+            }
+            SourceType::Bing(_) | SourceType::DuckDuckGo(_) | SourceType::Yahoo(_) | SourceType::Baidu(_) => {
+                Some(Duration::from_secs(120))
+            }
+            SourceType::SiteDossier(_) => Some(Duration::from_secs(120)),
+            SourceType::IntelX(_) => Some(Duration::from_secs(120)),
+            _ => None,
+        }
+    }
+
+    /// Hands the source the pool it should use for concurrent page fetches.
+    /// Sources that page strictly one after another ignore it.
+    pub fn set_page_pool(&mut self, pages: PagePool) {
+        match self {
+            SourceType::CommonCrawl(source) => source.set_page_pool(pages),
+            SourceType::RapidDNS(source) => source.set_page_pool(pages),
+            SourceType::GitHub(source) => source.set_page_pool(pages),
+            SourceType::GitLab(source) => source.set_page_pool(pages),
+            _ => {}
+        }
+    }
+
+    /// Tells the source the budget the runner will enforce. Plugins run on
+    /// a blocking thread the runner cannot cancel, so they stop themselves;
+    /// other sources are dropped at the timeout and ignore it.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        if let SourceType::Plugin(source) = self {
+            source.set_timeout(timeout);
+        }
+    }
+
+    /// Applies the source-specific sections of the config file
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        match self {
+            SourceType::CommonCrawl(source) => source.set_settings(settings.commoncrawl.clone()),
+            SourceType::WebArchive(source) => source.set_settings(settings.webarchive.clone()),
+            SourceType::CrtSh(source) => source.set_settings(settings.crtsh.clone()),
+            SourceType::GitHub(source) => source.set_settings(settings.github.clone()),
+            SourceType::GitLab(source) => source.set_settings(settings.gitlab.clone()),
+            SourceType::Censys(source) => source.set_settings(settings.censys.clone()),
+            SourceType::DNSDB(source) => source.set_settings(settings.dnsdb.clone()),
+            SourceType::Netlas(source) => source.set_settings(settings.netlas.clone()),
+            SourceType::Shodan(source) => source.set_settings(settings.shodan.clone()),
+            SourceType::SecurityTrails(source) => source.set_settings(settings.securitytrails.clone()),
+            SourceType::Bing(source)
+            | SourceType::DuckDuckGo(source)
+            | SourceType::Yahoo(source)
+            | SourceType::Baidu(source) => source.set_settings(settings.search_engines.clone()),
+            SourceType::Local(source) => source.set_settings(settings.local.clone()),
+            _ => {}
+        }
+    }
+
+    /// Builds the health-check probe for the source, or `None` when the
+    /// source needs a key and none is loaded or has nothing to probe
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        match self {
+            SourceType::CrtSh(source) => Some(source.probe(domain)),
+            SourceType::WebArchive(source) => Some(source.probe(domain)),
+            SourceType::Chaos(source) => source.probe(domain),
+            SourceType::GitHub(source) => source.probe(domain),
+            SourceType::GitLab(source) => source.probe(domain),
+            SourceType::DNSDB(source) => source.probe(domain),
+            SourceType::Censys(source) => source.probe(domain),
+            SourceType::AlienVault(source) => Some(source.probe(domain)),
+            SourceType::BufferOver(source) => Some(source.probe(domain)),
+            SourceType::CertSpotter(source) => source.probe(domain),
+            SourceType::ThreatCrowd(source) => Some(source.probe(domain)),
+            SourceType::VirusTotal(source) => source.probe(domain),
+            SourceType::HackerTarget(source) => Some(source.probe(domain)),
+            SourceType::Anubis(source) => Some(source.probe(domain)),
+            SourceType::RapidDNS(source) => Some(source.probe(domain)),
+            SourceType::DNSDumpster(source) => Some(source.probe(domain)),
+            SourceType::CommonCrawl(source) => Some(source.probe(domain)),
+            SourceType::Riddler(source) => Some(source.probe(domain)),
+            SourceType::Shodan(source) => source.probe(domain),
+            SourceType::SecurityTrails(source) => source.probe(domain),
+            SourceType::URLScan(source) => Some(source.probe(domain)),
+            SourceType::Bing(source) => Some(source.probe(domain)),
+            SourceType::DuckDuckGo(source) => Some(source.probe(domain)),
+            SourceType::Yahoo(source) => Some(source.probe(domain)),
+            SourceType::Baidu(source) => Some(source.probe(domain)),
+            SourceType::Facebook(source) => source.probe(domain),
+            SourceType::GoogleCT(source) => Some(source.probe(domain)),
+            SourceType::Robtex(source) => Some(source.probe(domain)),
+            SourceType::ThreatMiner(source) => Some(source.probe(domain)),
+            SourceType::SiteDossier(source) => Some(source.probe(domain)),
+            SourceType::BinaryEdge(source) => source.probe(domain),
+            SourceType::FullHunt(source) => source.probe(domain),
+            SourceType::Netlas(source) => source.probe(domain),
+            SourceType::LeakIX(source) => source.probe(domain),
+            SourceType::ZoomEye(source) => source.probe(domain),
+            SourceType::Quake(source) => source.probe(domain),
+            SourceType::Hunter(source) => source.probe(domain),
+            SourceType::Fofa(source) => source.probe(domain),
+            SourceType::WhoisXMLAPI(source) => source.probe(domain),
+            SourceType::C99(source) => source.probe(domain),
+            SourceType::IntelX(source) => source.probe(domain),
+            SourceType::Declarative(source) => source.probe(domain),
+            // Plugins make their own requests; there is nothing to probe
+            SourceType::Plugin(_) => None,
+            // Local datasets are read from disk; there is nothing to probe
+            SourceType::Local(_) => None,
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         match self {
             SourceType::CrtSh(source) => source.enumerate(domain).await,
             SourceType::WebArchive(source) => source.enumerate(domain).await,
             SourceType::Chaos(source) => source.enumerate(domain).await,
             SourceType::GitHub(source) => source.enumerate(domain).await,
+            SourceType::GitLab(source) => source.enumerate(domain).await,
             SourceType::DNSDB(source) => source.enumerate(domain).await,
             SourceType::Censys(source) => source.enumerate(domain).await,
             SourceType::AlienVault(source) => source.enumerate(domain).await,
@@ -194,19 +720,67 @@ impl SourceType {
             SourceType::DNSDumpster(source) => source.enumerate(domain).await,
             SourceType::CommonCrawl(source) => source.enumerate(domain).await,
             SourceType::Riddler(source) => source.enumerate(domain).await,
+            SourceType::Shodan(source) => source.enumerate(domain).await,
+            SourceType::SecurityTrails(source) => source.enumerate(domain).await,
+            SourceType::URLScan(source) => source.enumerate(domain).await,
+            SourceType::Bing(source) => source.enumerate(domain).await,
+            SourceType::DuckDuckGo(source) => source.enumerate(domain).await,
+            SourceType::Yahoo(source) => source.enumerate(domain).await,
+            SourceType::Baidu(source) => source.enumerate(domain).await,
+            SourceType::Facebook(source) => source.enumerate(domain).await,
+            SourceType::GoogleCT(source) => source.enumerate(domain).await,
+            SourceType::Robtex(source) => source.enumerate(domain).await,
+            SourceType::ThreatMiner(source) => source.enumerate(domain).await,
+            SourceType::SiteDossier(source) => source.enumerate(domain).await,
+            SourceType::BinaryEdge(source) => source.enumerate(domain).await,
+            SourceType::FullHunt(source) => source.enumerate(domain).await,
+            SourceType::Netlas(source) => source.enumerate(domain).await,
+            SourceType::LeakIX(source) => source.enumerate(domain).await,
+            SourceType::ZoomEye(source) => source.enumerate(domain).await,
+            SourceType::Quake(source) => source.enumerate(domain).await,
+            SourceType::Hunter(source) => source.enumerate(domain).await,
+            SourceType::Fofa(source) => source.enumerate(domain).await,
+            SourceType::WhoisXMLAPI(source) => source.enumerate(domain).await,
+            SourceType::C99(source) => source.enumerate(domain).await,
+            SourceType::IntelX(source) => source.enumerate(domain).await,
+            SourceType::Declarative(source) => source.enumerate(domain).await,
+            SourceType::Plugin(source) => source.enumerate(domain).await,
+            SourceType::Local(source) => source.enumerate(domain).await,
         }
     }
 }
 
+/// Which sources a run should use
+#[derive(Debug, Clone, Default)]
+pub struct SourceSelection {
+    /// Explicit source names; when non-empty only these are used
+    pub include: Vec<String>,
+    /// Source names to drop from the selected set
+    pub exclude: Vec<String>,
+    /// Use every source, including the opt-in ones
+    pub all: bool,
+    /// Use only default sources that answer with a single request
+    pub fast: bool,
+}
+
 pub struct SourceProvider;
 
 impl SourceProvider {
+    /// Whether `name` belongs to a built-in source, ignoring case
+    pub fn is_builtin(name: &str) -> bool {
+        // Building every source sets up its HTTP client, so do it once
+        static NAMES: OnceLock<Vec<String>> = OnceLock::new();
+        let names = NAMES.get_or_init(|| Self::get_sources().iter().map(SourceType::name).collect());
+        names.contains(&name.to_lowercase())
+    }
+
     pub fn get_sources() -> Vec<SourceType> {
         vec![
             SourceType::CrtSh(CrtShSource::new()),
             SourceType::WebArchive(WebArchiveSource::new()),
             SourceType::Chaos(ChaosSource::new()),
             SourceType::GitHub(GitHubSource::new()),
+            SourceType::GitLab(GitLabSource::new()),
             SourceType::DNSDB(DNSDBSource::new()),
             SourceType::Censys(CensysSource::new()),
             SourceType::AlienVault(AlienVaultSource::new()),
@@ -220,6 +794,30 @@ impl SourceProvider {
             SourceType::DNSDumpster(DNSDumpsterSource::new()),
             SourceType::CommonCrawl(CommonCrawlSource::new()),
             SourceType::Riddler(RiddlerSource::new()),
+            SourceType::Shodan(ShodanSource::new()),
+            SourceType::SecurityTrails(SecurityTrailsSource::new()),
+            SourceType::URLScan(URLScanSource::new()),
+            SourceType::Bing(SearchEngineSource::new(SearchEngine::Bing)),
+            SourceType::DuckDuckGo(SearchEngineSource::new(SearchEngine::DuckDuckGo)),
+            SourceType::Yahoo(SearchEngineSource::new(SearchEngine::Yahoo)),
+            SourceType::Baidu(SearchEngineSource::new(SearchEngine::Baidu)),
+            SourceType::Facebook(FacebookSource::new()),
+            SourceType::GoogleCT(GoogleCTSource::new()),
+            SourceType::Robtex(RobtexSource::new()),
+            SourceType::ThreatMiner(ThreatMinerSource::new()),
+            SourceType::SiteDossier(SiteDossierSource::new()),
+            SourceType::BinaryEdge(BinaryEdgeSource::new()),
+            SourceType::FullHunt(FullHuntSource::new()),
+            SourceType::Netlas(NetlasSource::new()),
+            SourceType::LeakIX(LeakIXSource::new()),
+            SourceType::ZoomEye(ZoomEyeSource::new()),
+            SourceType::Quake(QuakeSource::new()),
+            SourceType::Hunter(HunterSource::new()),
+            SourceType::Fofa(FofaSource::new()),
+            SourceType::WhoisXMLAPI(WhoisXMLAPISource::new()),
+            SourceType::C99(C99Source::new()),
+            SourceType::IntelX(IntelXSource::new()),
+            SourceType::Local(LocalSource::new()),
         ]
     }
 
@@ -228,19 +826,25 @@ impl SourceProvider {
 
         // Initialize each source with its API key if available
         let mut github = GitHubSource::new();
-        if let Some(key) = api_keys.get("github").and_then(|v| v.as_str()) {
-            github.add_api_keys(vec![key.to_string()]);
-        }
+        github.add_api_keys(string_keys(api_keys, "github"));
         sources.push(SourceType::GitHub(github));
 
+        let mut gitlab = GitLabSource::new();
+        gitlab.add_api_keys(string_keys(api_keys, "gitlab"));
+        sources.push(SourceType::GitLab(gitlab));
+
         let mut dnsdb = DNSDBSource::new();
-        if let Some(key) = api_keys.get("dnsdb").and_then(|v| v.as_str()) {
-            dnsdb.add_api_keys(vec![key.to_string()]);
-        }
+        dnsdb.add_api_keys(string_keys(api_keys, "dnsdb"));
         sources.push(SourceType::DNSDB(dnsdb));
 
         let mut censys = CensysSource::new();
-        if let Some(obj) = api_keys.get("censys").and_then(|v| v.as_object()) {
+        // Either one {"id", "secret"} object or a list of them
+        let censys_pairs = match api_keys.get("censys") {
+            Some(Value::Array(pairs)) => pairs.iter().collect(),
+            Some(pair) => vec![pair],
+            None => Vec::new(),
+        };
+        for obj in censys_pairs.into_iter().filter_map(|v| v.as_object()) {
             if let (Some(id), Some(secret)) = (
                 obj.get("id").and_then(|v| v.as_str()),
                 obj.get("secret").and_then(|v| v.as_str())
@@ -268,11 +872,136 @@ impl SourceProvider {
         }
         sources.push(SourceType::Chaos(chaos));
 
+        // Works without a key, a key raises the rate limits
+        let mut alienvault = AlienVaultSource::new();
+        if let Some(key) = api_keys.get("alienvault").and_then(|v| v.as_str()) {
+            alienvault.add_api_keys(vec![key.to_string()]);
+        }
+        sources.push(SourceType::AlienVault(alienvault));
+
+        let mut shodan = ShodanSource::new();
+        if let Some(key) = api_keys.get("shodan").and_then(|v| v.as_str()) {
+            shodan.add_api_keys(vec![key.to_string()]);
+        }
+        sources.push(SourceType::Shodan(shodan));
+
+        let mut securitytrails = SecurityTrailsSource::new();
+        securitytrails.add_api_keys(string_keys(api_keys, "securitytrails"));
+        sources.push(SourceType::SecurityTrails(securitytrails));
+
+        // Works without a key, a key raises the rate limits
+        let mut urlscan = URLScanSource::new();
+        if let Some(key) = api_keys.get("urlscan").and_then(|v| v.as_str()) {
+            urlscan.add_api_keys(vec![key.to_string()]);
+        }
+        sources.push(SourceType::URLScan(urlscan));
+
+        let mut facebook = FacebookSource::new();
+        // An app access token, given as "app_id|app_secret" or as an object
+        match api_keys.get("facebook") {
+            Some(Value::String(token)) => facebook.add_api_keys(vec![token.clone()]),
+            Some(Value::Object(obj)) => {
+                if let (Some(id), Some(secret)) = (
+                    obj.get("app_id").and_then(|v| v.as_str()),
+                    obj.get("app_secret").and_then(|v| v.as_str())
+                ) {
+                    facebook.add_api_keys(vec![format!("{}|{}", id, secret)]);
+                }
+            }
+            _ => {}
+        }
+        sources.push(SourceType::Facebook(facebook));
+
+        let mut binaryedge = BinaryEdgeSource::new();
+        binaryedge.add_api_keys(string_keys(api_keys, "binaryedge"));
+        sources.push(SourceType::BinaryEdge(binaryedge));
+
+        let mut fullhunt = FullHuntSource::new();
+        fullhunt.add_api_keys(string_keys(api_keys, "fullhunt"));
+        sources.push(SourceType::FullHunt(fullhunt));
+
+        let mut netlas = NetlasSource::new();
+        netlas.add_api_keys(string_keys(api_keys, "netlas"));
+        sources.push(SourceType::Netlas(netlas));
+
+        let mut leakix = LeakIXSource::new();
+        leakix.add_api_keys(string_keys(api_keys, "leakix"));
+        sources.push(SourceType::LeakIX(leakix));
+
+        let mut zoomeye = ZoomEyeSource::new();
+        zoomeye.add_api_keys(string_keys(api_keys, "zoomeye"));
+        sources.push(SourceType::ZoomEye(zoomeye));
+
+        let mut quake = QuakeSource::new();
+        quake.add_api_keys(string_keys(api_keys, "quake"));
+        sources.push(SourceType::Quake(quake));
+
+        let mut hunter = HunterSource::new();
+        hunter.add_api_keys(string_keys(api_keys, "hunter"));
+        sources.push(SourceType::Hunter(hunter));
+
+        let mut fofa = FofaSource::new();
+        // "email:key" strings or {"email", "key"} objects, alone or in a list;
+        // newer FOFA accounts only need the key
+        let fofa_keys = match api_keys.get("fofa") {
+            Some(Value::Array(keys)) => keys.iter().collect(),
+            Some(key) => vec![key],
+            None => Vec::new(),
+        };
+        for key in fofa_keys {
+            match key {
+                Value::String(key) => {
+                    let (email, key) = key.split_once(':').unwrap_or(("", key));
+                    fofa.add_api_keys(vec![(email.to_string(), key.to_string())]);
+                }
+                Value::Object(obj) => {
+                    if let Some(key) = obj.get("key").and_then(|v| v.as_str()) {
+                        let email = obj.get("email").and_then(|v| v.as_str()).unwrap_or_default();
+                        fofa.add_api_keys(vec![(email.to_string(), key.to_string())]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        sources.push(SourceType::Fofa(fofa));
+
+        let mut whoisxmlapi = WhoisXMLAPISource::new();
+        whoisxmlapi.add_api_keys(string_keys(api_keys, "whoisxmlapi"));
+        sources.push(SourceType::WhoisXMLAPI(whoisxmlapi));
+
+        let mut c99 = C99Source::new();
+        c99.add_api_keys(string_keys(api_keys, "c99"));
+        sources.push(SourceType::C99(c99));
+
+        let mut intelx = IntelXSource::new();
+        // "host:key" strings or {"host", "key"} objects, alone or in a list;
+        // a bare key goes to the free-tier host
+        let intelx_keys = match api_keys.get("intelx") {
+            Some(Value::Array(keys)) => keys.iter().collect(),
+            Some(key) => vec![key],
+            None => Vec::new(),
+        };
+        for key in intelx_keys {
+            match key {
+                Value::String(key) => {
+                    let (host, key) = key.split_once(':').unwrap_or((intelx::DEFAULT_HOST, key));
+                    intelx.add_api_keys(vec![(host.to_string(), key.to_string())]);
+                }
+                Value::Object(obj) => {
+                    if let Some(key) = obj.get("key").and_then(|v| v.as_str()) {
+                        let host = obj.get("host").and_then(|v| v.as_str()).unwrap_or(intelx::DEFAULT_HOST);
+                        intelx.add_api_keys(vec![(host.to_string(), key.to_string())]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        sources.push(SourceType::IntelX(intelx));
+
         // Add sources that don't require API keys
         sources.extend(vec![
             SourceType::CrtSh(CrtShSource::new()),
             SourceType::WebArchive(WebArchiveSource::new()),
-            SourceType::AlienVault(AlienVaultSource::new()),
             SourceType::BufferOver(BufferOverSource::new()),
             SourceType::ThreatCrowd(ThreatCrowdSource::new()),
             SourceType::HackerTarget(HackerTargetSource::new()),
@@ -281,8 +1010,142 @@ impl SourceProvider {
             SourceType::DNSDumpster(DNSDumpsterSource::new()),
             SourceType::CommonCrawl(CommonCrawlSource::new()),
             SourceType::Riddler(RiddlerSource::new()),
+            SourceType::Bing(SearchEngineSource::new(SearchEngine::Bing)),
+            SourceType::DuckDuckGo(SearchEngineSource::new(SearchEngine::DuckDuckGo)),
+            SourceType::Yahoo(SearchEngineSource::new(SearchEngine::Yahoo)),
+            SourceType::Baidu(SearchEngineSource::new(SearchEngine::Baidu)),
+            SourceType::GoogleCT(GoogleCTSource::new()),
+            SourceType::Robtex(RobtexSource::new()),
+            SourceType::ThreatMiner(ThreatMinerSource::new()),
+            SourceType::SiteDossier(SiteDossierSource::new()),
+            SourceType::Local(LocalSource::new()),
         ]);
 
         sources
     }
+
+    /// Builds the `[[declarative]]` sources of the config file
+    pub fn get_declarative(definitions: &[DeclarativeSettings], api_keys: Option<&Value>) -> Vec<SourceType> {
+        definitions
+            .iter()
+            .map(|definition| {
+                let name = definition.name.to_lowercase();
+                let mut source = DeclarativeSource::new(DeclarativeSettings { name, ..definition.clone() });
+                if let (Some(keys), Some(entry)) = (api_keys, definition.key.as_deref()) {
+                    source.add_api_keys(string_keys(keys, entry));
+                }
+                SourceType::Declarative(source)
+            })
+            .collect()
+    }
+
+    /// Builds the `[[plugins]]` sources of the config file
+    pub fn get_plugins(plugins: &[PluginSettings], api_keys: Option<&Value>) -> Vec<SourceType> {
+        plugins
+            .iter()
+            .map(|plugin| {
+                let name = plugin.name.to_lowercase();
+                let mut source = PluginSource::new(PluginSettings { name, ..plugin.clone() });
+                if let (Some(keys), Some(entry)) = (api_keys, plugin.key.as_deref()) {
+                    source.add_api_keys(string_keys(keys, entry));
+                }
+                SourceType::Plugin(source)
+            })
+            .collect()
+    }
+
+    /// Built-in sources plus the declarative and plugin ones from
+    /// `settings`. Name clashes are rejected by `Settings::load`.
+    pub fn get_configured(api_keys: Option<&Value>, settings: &Settings) -> Vec<SourceType> {
+        let mut sources = match api_keys {
+            Some(keys) => Self::get_sources_with_keys(keys),
+            None => Self::get_sources(),
+        };
+        sources.extend(Self::get_declarative(&settings.declarative, api_keys));
+        sources.extend(Self::get_plugins(&settings.plugins, api_keys));
+        sources
+    }
+
+    /// Filters `sources` down to the ones picked by `selection`. Naming a
+    /// source that does not exist is an error, so a typo cannot quietly
+    /// turn into a run with no sources.
+    pub fn select(sources: Vec<SourceType>, selection: &SourceSelection) -> anyhow::Result<Vec<SourceType>> {
+        let include: Vec<String> = selection.include.iter().map(|s| s.to_lowercase()).collect();
+        let exclude: Vec<String> = selection.exclude.iter().map(|s| s.to_lowercase()).collect();
+
+        let unknown: Vec<&str> = include
+            .iter()
+            .chain(exclude.iter())
+            .filter(|name| !sources.iter().any(|source| &source.name() == *name))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            anyhow::bail!("Unknown source(s): {} (see --list-sources)", unknown.join(", "));
+        }
+
+        Ok(sources
+            .into_iter()
+            .filter(|source| {
+                if !include.is_empty() {
+                    include.contains(&source.name())
+                } else if selection.all {
+                    true
+                } else if selection.fast {
+                    source.is_default() && source.is_fast()
+                } else {
+                    source.is_default()
+                }
+            })
+            .filter(|source| !exclude.contains(&source.name()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(sources: &[SourceType]) -> Vec<String> {
+        sources.iter().map(|source| source.name()).collect()
+    }
+
+    #[test]
+    fn select_rejects_unknown_names() {
+        let selection = SourceSelection {
+            include: vec!["crtsh".to_string(), "nosuchsource".to_string()],
+            ..Default::default()
+        };
+        match SourceProvider::select(SourceProvider::get_sources(), &selection) {
+            Err(e) => assert!(e.to_string().contains("nosuchsource")),
+            Ok(_) => panic!("unknown source was accepted"),
+        }
+
+        let selection = SourceSelection {
+            exclude: vec!["typo".to_string()],
+            ..Default::default()
+        };
+        assert!(SourceProvider::select(SourceProvider::get_sources(), &selection).is_err());
+    }
+
+    #[test]
+    fn partial_result_keeps_names_found_before_an_error() {
+        let found: HashSet<String> = ["a.example.com".to_string()].into();
+        assert_eq!(partial_result(found.clone(), Some(SourceError::Timeout)).unwrap(), found);
+        assert!(matches!(
+            partial_result(HashSet::<String>::new(), Some(SourceError::Timeout)),
+            Err(SourceError::Timeout)
+        ));
+        assert!(partial_result(HashSet::<String>::new(), None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn select_includes_and_excludes_by_name() {
+        let selection = SourceSelection {
+            include: vec!["CrtSh".to_string(), "hackertarget".to_string()],
+            exclude: vec!["hackertarget".to_string()],
+            ..Default::default()
+        };
+        let sources = SourceProvider::select(SourceProvider::get_sources(), &selection).unwrap();
+        assert_eq!(names(&sources), vec!["crtsh"]);
+    }
 }
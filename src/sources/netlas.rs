@@ -0,0 +1,155 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://app.netlas.io/api";
+
+/// `[netlas]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetlasSettings {
+    /// Upper bound on domains downloaded per run; every downloaded
+    /// domain is charged against the account's quota
+    pub max_results: usize,
+}
+
+impl Default for NetlasSettings {
+    fn default() -> Self {
+        Self { max_results: 1000 }
+    }
+}
+
+#[derive(Clone)]
+pub struct NetlasSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+    settings: NetlasSettings,
+}
+
+#[derive(Debug, Deserialize)]
+struct CountResponse {
+    count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct DomainItem {
+    data: DomainData,
+}
+
+#[derive(Debug, Deserialize)]
+struct DomainData {
+    domain: String,
+}
+
+// Every name under the domain, but not the domain itself
+fn domain_query(domain: &str) -> String {
+    format!("domain:*.{} AND NOT domain:{}", domain, domain)
+}
+
+impl NetlasSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+            settings: NetlasSettings::default(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn set_settings(&mut self, settings: NetlasSettings) {
+        self.settings = settings;
+    }
+
+    fn count_url(domain: &str) -> String {
+        format!("{}/domains_count/?q={}", API_URL, urlencoding::encode(&domain_query(domain)))
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(Self::count_url(domain))
+                .header("X-API-Key", api_key),
+            format: ProbeFormat::JsonField("count"),
+        })
+    }
+
+    async fn count(&self, api_key: &str, domain: &str) -> Result<usize, SourceError> {
+        let response = check_status(
+            self.client
+                .get(Self::count_url(domain))
+                .header("X-API-Key", api_key)
+                .send()
+                .await?,
+        )?;
+        let count: CountResponse = parse_json(&response.text().await?)?;
+        Ok(count.count)
+    }
+
+    /// Downloads every matching domain in one response, which avoids the
+    /// search endpoint's 10000-result paging cap
+    async fn download(&self, api_key: &str, domain: &str, size: usize) -> Result<Vec<DomainItem>, SourceError> {
+        let response = check_status(
+            self.client
+                .post(format!("{}/domains/download/", API_URL))
+                .header("X-API-Key", api_key)
+                .json(&serde_json::json!({
+                    "q": domain_query(domain),
+                    "fields": ["domain"],
+                    "source_type": "include",
+                    "size": size
+                }))
+                .send()
+                .await?,
+        )?;
+        parse_json(&response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying Netlas API for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+
+        let count = keys.with_key(|api_key| self.count(api_key, domain)).await?;
+        debug!("Netlas has {} domains for {}", count, domain);
+
+        let size = count.min(self.settings.max_results);
+        if size < count {
+            warn!("Netlas has {} domains for {}, downloading the first {} (max_results)", count, domain, size);
+        }
+        if size > 0 {
+            let items = keys.with_key(|api_key| self.download(api_key, domain, size)).await?;
+            for item in items {
+                let name = item.data.domain.to_lowercase();
+                if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                    results += 1;
+                }
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("Netlas finished: {} results in {:?}", results, elapsed);
+        Ok(subdomains)
+    }
+}
@@ -0,0 +1,609 @@
+//! Out-of-tree sources compiled to WebAssembly
+//!
+//! A plugin is a core wasm module exporting `memory`, `alloc(len) -> ptr`
+//! and `enumerate(domain_ptr, domain_len) -> i32` (0 on success). It gets
+//! no WASI, so no filesystem, clock or sockets; everything it can do goes
+//! through the `subfinder` imports below:
+//!
+//! - `emit(ptr, len)` reports one hostname
+//! - `log(ptr, len)` writes a debug log line
+//! - `key(ptr, len) -> i32` copies the plugin's API key, returning its full
+//!   length or -1 when none is loaded
+//! - `header(name_ptr, name_len, value_ptr, value_len)` adds a header to the
+//!   next request
+//! - `http_get(url_ptr, url_len) -> i32` fetches a URL, returning the HTTP
+//!   status or one of the negative `HTTP_*` codes. Redirects are only
+//!   followed to allowed hosts; any other redirect comes back as the 3xx.
+//! - `response(ptr, len) -> i32` copies the last response body, returning
+//!   its full length
+
+use log::{debug, warn};
+use reqwest::redirect::Policy;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use url::Url;
+use wasmtime::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, UpdateDeadline,
+};
+
+use crate::sources::{client_builder, is_valid_subdomain, partial_result, SourceError, SourceProvider};
+
+/// The request was refused by the plugin's policy
+const HTTP_DENIED: i32 = -1;
+/// The request failed before a response came back
+const HTTP_NETWORK: i32 = -2;
+/// The body was larger than `max_response_bytes`
+const HTTP_TOO_LARGE: i32 = -3;
+
+/// Budget used until the runner hands over its own
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often a running plugin looks at its deadline
+const EPOCH_TICK: Duration = Duration::from_millis(100);
+const MAX_REDIRECTS: usize = 10;
+
+/// One `[[plugins]]` entry of the config file
+///
+/// ```toml
+/// [[plugins]]
+/// name = "inventory"
+/// path = "plugins/inventory.wasm"
+/// allowed_hosts = ["inventory.corp.example", "*.pdns.corp.example"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginSettings {
+    pub name: String,
+    /// Compiled module, `.wasm` or `.wat`
+    pub path: PathBuf,
+    /// Keys file entry handed to the plugin through `key`
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Hosts the plugin may fetch from; `*.example.com` also matches
+    /// subdomains. Empty means no network access at all.
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    #[serde(default = "default_max_requests")]
+    pub max_requests: usize,
+    #[serde(default = "default_max_response_bytes")]
+    pub max_response_bytes: usize,
+    /// Linear memory cap in MiB
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: usize,
+    /// Instruction budget, roughly one unit per wasm instruction
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    #[serde(default = "default_true")]
+    pub default: bool,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+fn default_max_requests() -> usize {
+    100
+}
+
+fn default_max_response_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+fn default_memory_mb() -> usize {
+    64
+}
+
+fn default_fuel() -> u64 {
+    10_000_000_000
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl PluginSettings {
+    /// Catches definitions that could never work before a run starts
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.is_empty() || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("invalid plugin name '{}'", self.name));
+        }
+        if SourceProvider::is_builtin(&self.name) {
+            return Err(format!("'{}' is the name of a built-in source", self.name));
+        }
+        if !self.path.is_file() {
+            return Err(format!("{}: {} is not a file", self.name, self.path.display()));
+        }
+        Ok(())
+    }
+
+    fn allows(&self, url: &Url) -> bool {
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_lowercase();
+        self.allowed_hosts.iter().any(|allowed| {
+            let allowed = allowed.to_lowercase();
+            match allowed.strip_prefix("*.") {
+                Some(parent) => host == parent || host.ends_with(&format!(".{}", parent)),
+                None => host == allowed,
+            }
+        })
+    }
+}
+
+type Compiled = Result<(Engine, Module), String>;
+
+#[derive(Clone)]
+pub struct PluginSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+    settings: Arc<PluginSettings>,
+    timeout: Duration,
+    // Compiled on first use and shared by every enumeration of the run
+    compiled: Arc<OnceLock<Compiled>>,
+}
+
+/// Host side of one plugin call
+struct PluginState {
+    client: Arc<Client>,
+    settings: Arc<PluginSettings>,
+    key: Option<String>,
+    runtime: Handle,
+    headers: Vec<(String, String)>,
+    response: Vec<u8>,
+    requests: usize,
+    names: Vec<String>,
+    limits: StoreLimits,
+    deadline: Instant,
+}
+
+/// Advances the engine's epoch while a plugin runs, so compiled code
+/// regularly stops to check its deadline. Stops when dropped.
+struct EpochTicker {
+    stop: Arc<AtomicBool>,
+}
+
+impl EpochTicker {
+    fn start(engine: &Engine) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let engine = engine.clone();
+        let stopped = stop.clone();
+        thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            }
+        });
+        Self { stop }
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// Only follows redirects that stay within the plugin's allowed hosts; a
+// hop anywhere else hands the redirect itself back to the plugin
+fn redirect_policy(settings: Arc<PluginSettings>) -> Policy {
+    Policy::custom(move |attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if settings.allows(attempt.url()) {
+            attempt.follow()
+        } else {
+            warn!("Plugin {} was denied a redirect to {}", settings.name, attempt.url());
+            attempt.stop()
+        }
+    })
+}
+
+fn memory(caller: &mut Caller<'_, PluginState>) -> wasmtime::Result<Memory> {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => Ok(memory),
+        _ => Err(wasmtime::Error::msg("plugin does not export its memory")),
+    }
+}
+
+fn read_string(caller: &mut Caller<'_, PluginState>, ptr: i32, len: i32) -> wasmtime::Result<String> {
+    let memory = memory(caller)?;
+    let len = len as u32 as usize;
+    // Refuse before allocating a buffer for a length the guest cannot have
+    if len > memory.data_size(&caller) {
+        return Err(wasmtime::Error::msg("string runs past the end of memory"));
+    }
+    let mut buffer = vec![0; len];
+    memory.read(&caller, ptr as u32 as usize, &mut buffer)?;
+    Ok(String::from_utf8_lossy(&buffer).into_owned())
+}
+
+// Copies as much of `data` as fits and returns its full length, so the
+// plugin can retry with a bigger buffer
+fn write_bytes(caller: &mut Caller<'_, PluginState>, data: &[u8], ptr: i32, len: i32) -> wasmtime::Result<i32> {
+    let memory = memory(caller)?;
+    let count = data.len().min(len as u32 as usize);
+    memory.write(caller, ptr as u32 as usize, &data[..count])?;
+    Ok(data.len() as i32)
+}
+
+fn linker(engine: &Engine) -> wasmtime::Result<Linker<PluginState>> {
+    let mut linker = Linker::new(engine);
+
+    linker.func_wrap("subfinder", "emit", |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+        let name = read_string(&mut caller, ptr, len)?;
+        caller.data_mut().names.push(name);
+        Ok(())
+    })?;
+
+    linker.func_wrap("subfinder", "log", |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+        let line = read_string(&mut caller, ptr, len)?;
+        debug!("[{}] {}", caller.data().settings.name, line);
+        Ok(())
+    })?;
+
+    linker.func_wrap("subfinder", "key", |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+        match caller.data().key.clone() {
+            Some(key) => write_bytes(&mut caller, key.as_bytes(), ptr, len),
+            None => Ok(-1),
+        }
+    })?;
+
+    linker.func_wrap(
+        "subfinder",
+        "header",
+        |mut caller: Caller<'_, PluginState>, name_ptr: i32, name_len: i32, value_ptr: i32, value_len: i32| {
+            let name = read_string(&mut caller, name_ptr, name_len)?;
+            let value = read_string(&mut caller, value_ptr, value_len)?;
+            caller.data_mut().headers.push((name, value));
+            Ok(())
+        },
+    )?;
+
+    linker.func_wrap("subfinder", "http_get", |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+        let url = read_string(&mut caller, ptr, len)?;
+        let state = caller.data_mut();
+        let headers = std::mem::take(&mut state.headers);
+        state.response.clear();
+
+        // Epoch checks only happen in wasm code, so a plugin spending its
+        // time in requests is stopped here instead
+        let remaining = state.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(wasmtime::Error::msg("plugin ran past its deadline"));
+        }
+
+        let url = match Url::parse(&url) {
+            Ok(url) if state.settings.allows(&url) && state.requests < state.settings.max_requests => url,
+            _ => {
+                warn!("Plugin {} was denied a request to {}", state.settings.name, url);
+                return Ok(HTTP_DENIED);
+            }
+        };
+        state.requests += 1;
+
+        let limit = state.settings.max_response_bytes;
+        let mut request = state.client.get(url).timeout(remaining);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        // Plugins run on a blocking thread, so waiting here holds up nothing else
+        let fetched = state.runtime.block_on(async {
+            let mut response = request.send().await?;
+            let status = response.status().as_u16() as i32;
+            let mut body = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                if body.len() + chunk.len() > limit {
+                    return Ok::<_, reqwest::Error>((HTTP_TOO_LARGE, Vec::new()));
+                }
+                body.extend_from_slice(&chunk);
+            }
+            Ok((status, body))
+        });
+
+        match fetched {
+            Ok((status, body)) => {
+                state.response = body;
+                Ok(status)
+            }
+            Err(e) => {
+                debug!("Plugin {} request failed: {}", state.settings.name, e.without_url());
+                Ok(HTTP_NETWORK)
+            }
+        }
+    })?;
+
+    linker.func_wrap("subfinder", "response", |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+        let body = std::mem::take(&mut caller.data_mut().response);
+        let written = write_bytes(&mut caller, &body, ptr, len);
+        caller.data_mut().response = body;
+        written
+    })?;
+
+    Ok(linker)
+}
+
+impl PluginSource {
+    pub fn new(settings: PluginSettings) -> Self {
+        let settings = Arc::new(settings);
+        let client = client_builder()
+            .redirect(redirect_policy(settings.clone()))
+            .build()
+            .expect("Failed to build HTTP client");
+        Self {
+            client: Arc::new(client),
+            api_keys: Vec::new(),
+            settings,
+            timeout: DEFAULT_TIMEOUT,
+            compiled: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.settings.name
+    }
+
+    pub fn requires_key(&self) -> bool {
+        self.settings.key.is_some()
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.settings.default
+    }
+
+    pub fn supports_recursive(&self) -> bool {
+        self.settings.recursive
+    }
+
+    fn compile(&self) -> &Compiled {
+        self.compiled.get_or_init(|| {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            config.epoch_interruption(true);
+            let engine = Engine::new(&config).map_err(|e| e.to_string())?;
+            let module = Module::from_file(&engine, &self.settings.path)
+                .map_err(|e| format!("failed to load {}: {}", self.settings.path.display(), e))?;
+            Ok((engine, module))
+        })
+    }
+
+    /// Instantiates the module and runs its `enumerate` export, returning
+    /// every name it emitted along with the error it stopped on, if any
+    fn run(&self, domain: &str, runtime: Handle) -> Result<(Vec<String>, Option<SourceError>), SourceError> {
+        let deadline = Instant::now() + self.timeout;
+        let (engine, module) = self.compile().as_ref().map_err(|e| SourceError::Plugin(e.clone()))?;
+        let plugin_error = |e: wasmtime::Error| {
+            if Instant::now() >= deadline {
+                SourceError::Timeout
+            } else {
+                SourceError::Plugin(e.root_cause().to_string())
+            }
+        };
+
+        let state = PluginState {
+            client: self.client.clone(),
+            settings: self.settings.clone(),
+            key: self.api_keys.first().cloned(),
+            runtime,
+            headers: Vec::new(),
+            response: Vec::new(),
+            requests: 0,
+            names: Vec::new(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(self.settings.memory_mb * 1024 * 1024)
+                .build(),
+            deadline,
+        };
+        let mut store = Store::new(engine, state);
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(self.settings.fuel).map_err(plugin_error)?;
+        // The runner's timeout cannot stop this thread, so the plugin is
+        // interrupted on the next epoch tick past the deadline
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(|store| {
+            if Instant::now() >= store.data().deadline {
+                Ok(UpdateDeadline::Interrupt)
+            } else {
+                Ok(UpdateDeadline::Continue(1))
+            }
+        });
+        let _ticker = EpochTicker::start(engine);
+
+        let instance = linker(engine)
+            .and_then(|linker| linker.instantiate(&mut store, module))
+            .map_err(plugin_error)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| SourceError::Plugin("plugin does not export its memory".to_string()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(plugin_error)?;
+        let enumerate = instance
+            .get_typed_func::<(i32, i32), i32>(&mut store, "enumerate")
+            .map_err(plugin_error)?;
+
+        let len = domain.len() as i32;
+        let ptr = alloc.call(&mut store, len).map_err(plugin_error)?;
+        memory
+            .write(&mut store, ptr as u32 as usize, domain.as_bytes())
+            .map_err(|e| SourceError::Plugin(e.to_string()))?;
+
+        // Names emitted before a trap, the deadline or an error status are
+        // still worth keeping, so they are taken before looking at the outcome
+        let called = enumerate.call(&mut store, (ptr, len));
+        let names = std::mem::take(&mut store.data_mut().names);
+        let error = match called {
+            Ok(0) => None,
+            Ok(status) => Some(SourceError::Plugin(format!("enumerate returned {}", status))),
+            Err(e) => Some(plugin_error(e)),
+        };
+        if let Some(e) = &error {
+            warn!("Plugin {} failed ({}) after emitting {} names", self.settings.name, e, names.len());
+        }
+        Ok((names, error))
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.settings.key.is_some() && self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+
+        debug!("Running plugin {} for domain: {}", self.settings.name, domain);
+
+        let plugin = self.clone();
+        let target = domain.to_string();
+        let runtime = Handle::current();
+        let (names, last_error) = tokio::task::spawn_blocking(move || plugin.run(&target, runtime))
+            .await
+            .map_err(|e| SourceError::Plugin(e.to_string()))??;
+
+        let mut subdomains = HashSet::new();
+        for name in names {
+            let name = name.trim().trim_start_matches("*.").to_lowercase();
+            if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                results += 1;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("{} finished: {} results in {:?}", self.settings.name, results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::mock::{serve, MockResponse};
+    use std::sync::Mutex;
+
+    fn plugin(name: &str, wat: &str, allowed_hosts: &[&str]) -> (PluginSource, PathBuf) {
+        let path = std::env::temp_dir().join(format!("subfinder-plugin-{}-{}.wat", name, std::process::id()));
+        std::fs::write(&path, wat).unwrap();
+        let source = PluginSource::new(PluginSettings {
+            name: name.to_string(),
+            path: path.clone(),
+            key: None,
+            allowed_hosts: allowed_hosts.iter().map(|host| host.to_string()).collect(),
+            max_requests: default_max_requests(),
+            max_response_bytes: default_max_response_bytes(),
+            memory_mb: default_memory_mb(),
+            fuel: u64::MAX,
+            default: true,
+            recursive: false,
+        });
+        (source, path)
+    }
+
+    // Fetches `url` and emits "followed" on a 200 or "stopped" on a 302
+    fn fetching_plugin(url: &str) -> String {
+        format!(
+            r#"(module
+  (import "subfinder" "http_get" (func $get (param i32 i32) (result i32)))
+  (import "subfinder" "emit" (func $emit (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "followed.example.com")
+  (data (i32.const 32) "stopped.example.com")
+  (data (i32.const 64) "{}")
+  (func (export "alloc") (param i32) (result i32) i32.const 1024)
+  (func (export "enumerate") (param i32 i32) (result i32)
+    (local $status i32)
+    (local.set $status (call $get (i32.const 64) (i32.const {})))
+    (if (i32.eq (local.get $status) (i32.const 200)) (then (call $emit (i32.const 0) (i32.const 20))))
+    (if (i32.eq (local.get $status) (i32.const 302)) (then (call $emit (i32.const 32) (i32.const 19))))
+    i32.const 0))"#,
+            url,
+            url.len()
+        )
+    }
+
+    #[tokio::test]
+    async fn deadline_stops_a_spinning_plugin() {
+        let wat = r#"(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) i32.const 0)
+  (func (export "enumerate") (param i32 i32) (result i32)
+    (loop $spin (br $spin))
+    i32.const 0))"#;
+        let (mut source, path) = plugin("spin", wat, &[]);
+        source.set_timeout(Duration::from_millis(300));
+
+        let start = Instant::now();
+        let result = source.enumerate("example.com").await;
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(SourceError::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(5), "took {:?}", start.elapsed());
+    }
+
+    #[tokio::test]
+    async fn names_emitted_before_the_deadline_are_kept() {
+        let wat = r#"(module
+  (import "subfinder" "emit" (func $emit (param i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "early.example.com")
+  (func (export "alloc") (param i32) (result i32) i32.const 1024)
+  (func (export "enumerate") (param i32 i32) (result i32)
+    (call $emit (i32.const 0) (i32.const 17))
+    (loop $spin (br $spin))
+    i32.const 0))"#;
+        let (mut source, path) = plugin("emit-then-spin", wat, &[]);
+        source.set_timeout(Duration::from_millis(300));
+
+        let names = source.enumerate("example.com").await;
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(names.unwrap(), HashSet::from(["early.example.com".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn redirects_only_reach_allowed_hosts() {
+        let outside_hits = Arc::new(Mutex::new(0));
+        let hits = outside_hits.clone();
+        let outside = serve(move |_| {
+            *hits.lock().unwrap() += 1;
+            MockResponse::new(200, "secret")
+        })
+        .await;
+        // Same server, but a host the plugin was not given
+        let outside = outside.replace("127.0.0.1", "localhost");
+
+        let base = serve(move |request| match request.target.as_str() {
+            "/hop" => MockResponse::new(302, "").header("Location", "/final"),
+            "/final" => MockResponse::new(200, "ok"),
+            _ => MockResponse::new(302, "").header("Location", &format!("{}/secret", outside)),
+        })
+        .await;
+
+        let (source, path) = plugin("redirect-allowed", &fetching_plugin(&format!("{}/hop", base)), &["127.0.0.1"]);
+        let names = source.enumerate("example.com").await;
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(names.unwrap(), HashSet::from(["followed.example.com".to_string()]));
+
+        let (source, path) = plugin("redirect-denied", &fetching_plugin(&format!("{}/leave", base)), &["127.0.0.1"]);
+        let names = source.enumerate("example.com").await;
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(names.unwrap(), HashSet::from(["stopped.example.com".to_string()]));
+        assert_eq!(*outside_hits.lock().unwrap(), 0);
+    }
+}
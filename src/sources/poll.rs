@@ -0,0 +1,148 @@
+use log::{debug, warn};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::sources::SourceError;
+
+/// What one poll of a submitted search came back with
+pub(crate) enum PollStatus<T> {
+    /// Nothing new yet, ask again after the poll interval
+    Pending,
+    /// A batch of results; more may follow after the poll interval
+    Partial(T),
+    /// The last batch; the search is finished
+    Done(T),
+}
+
+/// How often and for how long a submitted search is polled
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PollSchedule {
+    pub interval: Duration,
+    pub max_wait: Duration,
+}
+
+/// Runs a two-phase search: `submit` starts it and returns an id, then
+/// `poll` is called with that id until it reports `Done`
+///
+/// Batches are returned in the order they arrived. Running out of
+/// `max_wait` keeps whatever came back so far and only fails when that
+/// is nothing.
+pub(crate) async fn submit_and_poll<I, T, S, SFut, P, PFut>(
+    schedule: PollSchedule,
+    submit: S,
+    mut poll: P,
+) -> Result<Vec<T>, SourceError>
+where
+    I: Clone + std::fmt::Display,
+    S: FnOnce() -> SFut,
+    SFut: Future<Output = Result<I, SourceError>>,
+    P: FnMut(I) -> PFut,
+    PFut: Future<Output = Result<PollStatus<T>, SourceError>>,
+{
+    let id = submit().await?;
+    debug!("Search {} submitted, polling for results", id);
+
+    let deadline = Instant::now() + schedule.max_wait;
+    let mut batches = Vec::new();
+
+    loop {
+        match poll(id.clone()).await? {
+            PollStatus::Done(batch) => {
+                batches.push(batch);
+                return Ok(batches);
+            }
+            PollStatus::Partial(batch) => batches.push(batch),
+            PollStatus::Pending => {}
+        }
+        // Upstreams can keep answering "in progress" with empty batches,
+        // so every round waits out the interval
+        if Instant::now() + schedule.interval > deadline {
+            break;
+        }
+        tokio::time::sleep(schedule.interval).await;
+    }
+
+    if batches.is_empty() {
+        return Err(SourceError::Timeout);
+    }
+    warn!("Search {} still running after {:?}, keeping {} batches", id, schedule.max_wait, batches.len());
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const SCHEDULE: PollSchedule = PollSchedule {
+        interval: Duration::from_millis(20),
+        max_wait: Duration::from_millis(200),
+    };
+    // max_wait / interval, plus the first poll
+    const MAX_POLLS: usize = 11;
+
+    // Answers the n-th poll with `status(n)` and counts the polls
+    async fn run<F>(status: F) -> (Result<Vec<Vec<u32>>, SourceError>, usize)
+    where
+        F: Fn(usize) -> PollStatus<Vec<u32>>,
+    {
+        let calls = AtomicUsize::new(0);
+        let result = submit_and_poll(
+            SCHEDULE,
+            || async { Ok("search-1") },
+            |_| {
+                let status = status(calls.fetch_add(1, Ordering::SeqCst));
+                async move { Ok(status) }
+            },
+        )
+        .await;
+        (result, calls.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn done_returns_its_batch() {
+        let (result, calls) = run(|_| PollStatus::Done(vec![7])).await;
+        assert_eq!(result.unwrap(), vec![vec![7]]);
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn partial_batches_are_kept_until_done() {
+        let start = Instant::now();
+        let (result, calls) = run(|call| match call {
+            0 => PollStatus::Partial(vec![1]),
+            1 => PollStatus::Partial(vec![2]),
+            _ => PollStatus::Done(vec![3]),
+        })
+        .await;
+        assert_eq!(result.unwrap(), vec![vec![1], vec![2], vec![3]]);
+        assert_eq!(calls, 3);
+        // Each partial batch waits out the interval before the next poll
+        assert!(start.elapsed() >= SCHEDULE.interval * 2);
+    }
+
+    #[tokio::test]
+    async fn empty_partials_do_not_spin() {
+        let (result, calls) = run(|_| PollStatus::Partial(Vec::new())).await;
+        assert!(result.is_ok());
+        assert!(calls <= MAX_POLLS, "polled {} times", calls);
+    }
+
+    #[tokio::test]
+    async fn deadline_keeps_what_arrived() {
+        let (result, calls) = run(|call| match call {
+            0 => PollStatus::Partial(vec![1]),
+            _ => PollStatus::Pending,
+        })
+        .await;
+        assert_eq!(result.unwrap(), vec![vec![1]]);
+        assert!(calls <= MAX_POLLS, "polled {} times", calls);
+    }
+
+    #[tokio::test]
+    async fn deadline_without_results_is_a_timeout() {
+        let (result, calls) = run(|_| PollStatus::Pending).await;
+        assert!(matches!(result, Err(SourceError::Timeout)));
+        assert!(calls <= MAX_POLLS, "polled {} times", calls);
+    }
+}
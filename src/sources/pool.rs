@@ -0,0 +1,78 @@
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Page requests a source may have in flight when nothing else is configured
+pub const DEFAULT_PAGE_CONCURRENCY: usize = 4;
+
+/// Limits how many page or index requests a source runs at once
+///
+/// The runner already holds one permit of its global semaphore for each
+/// running source, so the first request in flight rides on that. Any extra
+/// request borrows a spare permit from the same semaphore, which keeps the
+/// whole run within `--threads` while letting a source fan out when other
+/// sources are idle.
+#[derive(Clone)]
+pub struct PagePool {
+    shared: Option<Arc<Semaphore>>,
+    cap: usize,
+}
+
+impl Default for PagePool {
+    fn default() -> Self {
+        Self {
+            shared: None,
+            cap: DEFAULT_PAGE_CONCURRENCY,
+        }
+    }
+}
+
+impl PagePool {
+    pub fn new(shared: Option<Arc<Semaphore>>, cap: usize) -> Self {
+        Self {
+            shared,
+            cap: cap.max(1),
+        }
+    }
+
+    /// Runs `fetch` for every page and returns the outputs in completion order
+    pub async fn fetch_all<P, F, Fut, T>(&self, pages: Vec<P>, fetch: F) -> Vec<T>
+    where
+        F: Fn(P) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let mut pending = pages.into_iter();
+        let mut in_flight = FuturesUnordered::new();
+        let mut outputs = Vec::new();
+
+        loop {
+            while in_flight.len() < self.cap {
+                // Without a shared semaphore only the cap applies
+                let permit = match (&self.shared, in_flight.is_empty()) {
+                    (Some(shared), false) => match shared.clone().try_acquire_owned() {
+                        Ok(permit) => Some(permit),
+                        Err(_) => break,
+                    },
+                    _ => None,
+                };
+                let Some(page) = pending.next() else {
+                    break;
+                };
+                let request = fetch(page);
+                in_flight.push(async move {
+                    let output = request.await;
+                    drop(permit);
+                    output
+                });
+            }
+
+            match in_flight.next().await {
+                Some(output) => outputs.push(output),
+                None => break,
+            }
+        }
+
+        outputs
+    }
+}
@@ -0,0 +1,176 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://quake.360.net/api/v3/search/quake_service";
+const PAGE_SIZE: usize = 500;
+const MAX_PAGES: usize = 10;
+
+#[derive(Clone)]
+pub struct QuakeSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuakeResponse {
+    // 0 on success, otherwise a string code such as "q3005"
+    code: Value,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    data: Vec<QuakeService>,
+    #[serde(default)]
+    meta: QuakeMeta,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct QuakeMeta {
+    #[serde(default)]
+    pagination: QuakePagination,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct QuakePagination {
+    #[serde(default)]
+    total: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct QuakeService {
+    #[serde(default)]
+    domain: Option<String>,
+    #[serde(default)]
+    service: Option<Value>,
+}
+
+impl QuakeService {
+    fn names(&self) -> impl Iterator<Item = &str> {
+        let host = self
+            .service
+            .as_ref()
+            .and_then(|service| service.pointer("/http/host"))
+            .and_then(|host| host.as_str());
+        self.domain.as_deref().into_iter().chain(host)
+    }
+}
+
+// Quake answers errors with a 200 and a code in the body
+fn body_error(code: &Value, message: &str) -> Option<SourceError> {
+    match code {
+        Value::Number(n) if n.as_i64() == Some(0) => None,
+        Value::String(code) => Some(match code.as_str() {
+            // q3005: too many requests, u3011: out of credits
+            "q3005" | "u3011" => SourceError::RateLimited,
+            // u3004: token missing, u3007: token invalid
+            "u3004" | "u3007" => SourceError::Auth(401),
+            _ => SourceError::Parse(format!("{}: {}", code, message)),
+        }),
+        _ => Some(SourceError::Parse(format!("{}: {}", code, message))),
+    }
+}
+
+fn request_body(domain: &str, start: usize, size: usize) -> Value {
+    serde_json::json!({
+        "query": format!("domain:\"{}\"", domain),
+        "include": ["domain", "service.http.host"],
+        "latest": true,
+        "start": start,
+        "size": size
+    })
+}
+
+impl QuakeSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .post(API_URL)
+                .header("X-QuakeToken", api_key)
+                .json(&request_body(domain, 0, 1)),
+            format: ProbeFormat::JsonField("data"),
+        })
+    }
+
+    async fn fetch_page(&self, api_key: &str, domain: &str, start: usize) -> Result<QuakeResponse, SourceError> {
+        let response = check_status(
+            self.client
+                .post(API_URL)
+                .header("X-QuakeToken", api_key)
+                .json(&request_body(domain, start, PAGE_SIZE))
+                .send()
+                .await?,
+        )?;
+        let data: QuakeResponse = parse_json(&response.text().await?)?;
+        match body_error(&data.code, &data.message) {
+            Some(e) => Err(e),
+            None => Ok(data),
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying Quake API for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        for page in 0..MAX_PAGES {
+            let start = page * PAGE_SIZE;
+            let data = match keys.with_key(|api_key| self.fetch_page(api_key, domain, start)).await {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Quake stopped at offset {}: {}", start, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            for service in &data.data {
+                for name in service.names() {
+                    let name = name.to_lowercase();
+                    if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                        results += 1;
+                    }
+                }
+            }
+
+            if data.data.len() < PAGE_SIZE || start + PAGE_SIZE >= data.meta.pagination.total {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("Quake finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
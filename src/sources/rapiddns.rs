@@ -1,4 +1,3 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use scraper::{Html, Selector};
@@ -6,114 +5,112 @@ use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain, is_html_response};
+use crate::sources::{check_status, create_client, is_html_response, is_valid_subdomain, partial_result, PagePool, Probe, ProbeFormat, SourceError};
 
 #[derive(Clone)]
 pub struct RapidDNSSource {
     client: Arc<Client>,
+    pages: PagePool,
 }
 
 impl RapidDNSSource {
     pub fn new() -> Self {
         Self {
             client: create_client(),
+            pages: PagePool::default(),
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub fn set_page_pool(&mut self, pages: PagePool) {
+        self.pages = pages;
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self
+                .client
+                .get(format!("https://rapiddns.io/subdomain/{}?page=1&full=1", domain)),
+            format: ProbeFormat::Html,
+        }
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<String, SourceError> {
+        let response = check_status(self.client.get(url).send().await?)?;
+        let text = response.text().await?;
+
+        // RapidDNS is scraped, so anything but an HTML page means the site changed
+        if !is_html_response(&text) {
+            return Err(SourceError::Parse("expected an HTML page".to_string()));
+        }
+        Ok(text)
+    }
+
+    fn page_url(domain: &str, page: usize) -> String {
+        format!("https://rapiddns.io/subdomain/{}?page={}&full=1", domain, page)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         debug!("Querying RapidDNS for domain: {}", domain);
 
         let mut subdomains = HashSet::new();
-        let mut page = 1;
         let mut max_pages = 1;
 
         // Regex for extracting max page number
-        let page_pattern = regex::Regex::new(r#"class="page-link" href="/subdomain/[^"]+\?page=(\d+)""#)?;
+        let page_pattern = regex::Regex::new(r#"class="page-link" href="/subdomain/[^"]+\?page=(\d+)""#)
+            .map_err(|e| SourceError::Parse(e.to_string()))?;
 
         // Prepare selector once
-        let row_selector = match Selector::parse("table#table > tbody > tr > td:first-child") {
-            Ok(selector) => selector,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse row selector: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        loop {
-            let url = format!("https://rapiddns.io/subdomain/{}?page={}&full=1", domain, page);
-            let response = match self.client
-                .get(&url)
-                .send()
-                .await
-            {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        errors += 1;
-                        warn!("RapidDNS returned error status: {}", resp.status());
-                        break;
-                    }
-                    resp
-                }
-                Err(e) => {
-                    errors += 1;
-                    warn!("Failed to query RapidDNS page {}: {}", page, e);
-                    break;
-                }
-            };
-
-            let text = match response.text().await {
-                Ok(t) => t,
-                Err(e) => {
-                    errors += 1;
-                    warn!("Failed to read RapidDNS response for page {}: {}", page, e);
-                    break;
-                }
-            };
-
-            // Check if it's a valid HTML response
-            if !is_html_response(&text) {
-                debug!("Received non-HTML response from RapidDNS, skipping...");
-                break;
-            }
+        let row_selector = Selector::parse("table#table > tbody > tr > td:first-child")
+            .map_err(|e| SourceError::Parse(e.to_string()))?;
 
-            // Parse the table rows which contain subdomains
-            let document = Html::parse_document(&text);
+        // Parse the table rows which contain subdomains
+        let mut collect_page = |text: &str| {
+            let document = Html::parse_document(text);
             for element in document.select(&row_selector) {
                 let subdomain = element.text().collect::<String>().trim().to_lowercase();
-                if !subdomain.is_empty() && is_valid_subdomain(&subdomain, domain) {
+                if !subdomain.is_empty() && is_valid_subdomain(&subdomain, domain) && subdomains.insert(subdomain) {
                     results += 1;
-                    subdomains.insert(subdomain);
                 }
             }
+        };
 
-            // Update max pages on first page
-            if page == 1 {
-                if let Some(captures) = page_pattern.captures_iter(&text).last() {
-                    if let Some(last_page) = captures.get(1) {
-                        if let Ok(num) = last_page.as_str().parse::<usize>() {
-                            max_pages = num;
-                            debug!("Found {} total pages on RapidDNS", max_pages);
-                        }
-                    }
+        // The first page tells us how many pages there are
+        let text = self.fetch_page(&Self::page_url(domain, 1)).await?;
+        collect_page(&text);
+
+        if let Some(captures) = page_pattern.captures_iter(&text).last() {
+            if let Some(last_page) = captures.get(1) {
+                if let Ok(num) = last_page.as_str().parse::<usize>() {
+                    max_pages = num;
+                    debug!("Found {} total pages on RapidDNS", max_pages);
                 }
             }
+        }
 
-            if page >= max_pages {
-                break;
+        // Fetch the remaining pages concurrently, keeping whatever succeeds
+        let mut last_error = None;
+        let pages = self
+            .pages
+            .fetch_all((2..=max_pages).collect(), |page| async move {
+                (page, self.fetch_page(&Self::page_url(domain, page)).await)
+            })
+            .await;
+
+        for (page, result) in pages {
+            match result {
+                Ok(text) => collect_page(&text),
+                Err(e) => {
+                    warn!("RapidDNS page {} failed: {}", page, e);
+                    last_error = Some(e);
+                }
             }
-            page += 1;
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "RapidDNS finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("RapidDNS finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
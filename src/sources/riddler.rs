@@ -1,11 +1,10 @@
-use anyhow::{Context, Result};
-use log::{debug, warn};
+use log::debug;
 use reqwest::Client;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, create_client, is_html_response, is_valid_subdomain, Probe, ProbeFormat, SourceError};
 
 #[derive(Clone)]
 pub struct RiddlerSource {
@@ -19,10 +18,18 @@ impl RiddlerSource {
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self
+                .client
+                .get(format!("https://riddler.io/search/exportcsv?q=pld:{}", domain)),
+            format: ProbeFormat::Text,
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         debug!("Querying Riddler for domain: {}", domain);
 
@@ -32,44 +39,28 @@ impl RiddlerSource {
             format!("http://riddler.io/search/exportcsv?q=pld:{}", domain),
         ];
 
+        let mut last_error = SourceError::Network("no Riddler endpoint tried".to_string());
         let mut final_response = None;
         for url in urls {
-            let result = self.client
-                .get(&url)
-                .send()
-                .await;
-            
-            match result {
+            let result = self.client.get(&url).send().await;
+
+            match result.map_err(SourceError::from).and_then(check_status) {
                 Ok(resp) => {
-                    if resp.status().is_success() {
-                        final_response = Some(resp);
-                        break;
-                    }
-                    debug!("Riddler endpoint {} returned status: {}", url, resp.status());
+                    final_response = Some(resp);
+                    break;
                 }
                 Err(e) => {
-                    debug!("Failed to query Riddler endpoint {}: {}", url, e);
+                    debug!("Riddler endpoint {} failed: {}", url, e);
+                    last_error = e;
                 }
             }
         }
 
-        let response = match final_response {
-            Some(resp) => resp,
-            None => {
-                errors += 1;
-                warn!("Failed to query all Riddler endpoints");
-                return Ok(HashSet::new());
-            }
-        };
-
-        let text = match response.text().await {
-            Ok(t) => t,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to read Riddler response: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
+        let response = final_response.ok_or(last_error)?;
+        let text = response.text().await?;
+        if is_html_response(&text) {
+            return Err(SourceError::UpstreamHtml);
+        }
 
         let mut subdomains = HashSet::new();
 
@@ -83,10 +74,7 @@ impl RiddlerSource {
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "Riddler finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
+        debug!("Riddler finished: {} results in {:?}", results, elapsed);
         Ok(subdomains)
     }
 }
@@ -0,0 +1,138 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, for_each_line, is_valid_subdomain, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://freeapi.robtex.com/pdns";
+// The free API allows a handful of queries per minute; reverse lookups
+// beyond this rarely add names anyway
+const MAX_REVERSE_LOOKUPS: usize = 10;
+
+#[derive(Clone)]
+pub struct RobtexSource {
+    client: Arc<Client>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PdnsRecord {
+    rrname: String,
+    rrdata: String,
+    rrtype: String,
+}
+
+fn normalize(name: &str, domain: &str) -> Option<String> {
+    let name = name.trim_end_matches('.').to_lowercase();
+    is_valid_subdomain(&name, domain).then_some(name)
+}
+
+/// Names in the forward records, and the addresses they point at
+fn read_forward(records: &[PdnsRecord], domain: &str) -> (HashSet<String>, Vec<String>) {
+    let mut names = HashSet::new();
+    let mut addresses = Vec::new();
+    for record in records {
+        names.extend(normalize(&record.rrname, domain));
+        match record.rrtype.as_str() {
+            "A" | "AAAA" => {
+                if record.rrdata.parse::<IpAddr>().is_ok() && !addresses.contains(&record.rrdata) {
+                    addresses.push(record.rrdata.clone());
+                }
+            }
+            _ => names.extend(normalize(&record.rrdata, domain)),
+        }
+    }
+    (names, addresses)
+}
+
+impl RobtexSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+        }
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(format!("{}/forward/{}", API_URL, domain)),
+            format: ProbeFormat::Text,
+        }
+    }
+
+    /// Streams one pDNS query; Robtex answers with one JSON record per line
+    async fn query(&self, url: &str) -> Result<Vec<PdnsRecord>, SourceError> {
+        let response = check_status(self.client.get(url).send().await?)?;
+        let mut records = Vec::new();
+        for_each_line(response, |line| {
+            if let Ok(record) = serde_json::from_str::<PdnsRecord>(line) {
+                records.push(record);
+            }
+        })
+        .await?;
+        Ok(records)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        debug!("Querying Robtex for domain: {}", domain);
+
+        let records = self.query(&format!("{}/forward/{}", API_URL, domain)).await?;
+        let (mut subdomains, addresses) = read_forward(&records, domain);
+        results += subdomains.len();
+
+        // Other names hosted on the same addresses
+        for address in addresses.iter().take(MAX_REVERSE_LOOKUPS) {
+            match self.query(&format!("{}/reverse/{}", API_URL, address)).await {
+                Ok(records) => {
+                    for name in records.iter().filter_map(|record| normalize(&record.rrname, domain)) {
+                        if subdomains.insert(name) {
+                            results += 1;
+                        }
+                    }
+                }
+                // Reverse lookups only add to what the forward one found
+                Err(e) => {
+                    warn!("Robtex reverse lookup for {} failed: {}", address, e);
+                    break;
+                }
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("Robtex finished: {} results in {:?}", results, elapsed);
+        Ok(subdomains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(jsonl: &str) -> Vec<PdnsRecord> {
+        jsonl.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+    }
+
+    #[test]
+    fn forward_records_give_names_and_addresses() {
+        let records = records(include_str!("../../tests/fixtures/robtex/forward.jsonl"));
+        let (names, addresses) = read_forward(&records, "example.com");
+        let expected: HashSet<String> = ["www.example.com", "mail.example.com", "cdn.example.com"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(names, expected);
+        assert_eq!(addresses, vec!["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"]);
+    }
+
+    #[test]
+    fn reverse_records_only_add_in_scope_names() {
+        let records = records(include_str!("../../tests/fixtures/robtex/reverse.jsonl"));
+        let names: HashSet<String> = records.iter().filter_map(|record| normalize(&record.rrname, "example.com")).collect();
+        assert_eq!(names, ["legacy.example.com".to_string()].into());
+    }
+}
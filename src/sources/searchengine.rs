@@ -0,0 +1,343 @@
+use log::{debug, warn};
+use reqwest::Client;
+use scraper::{Html, Selector};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::sources::{check_status, create_client, extract_subdomains, is_html_response, partial_result, subdomain_pattern, Probe, ProbeFormat, SourceError};
+
+/// `[search_engines]` section of the config file, shared by every engine
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchEngineSettings {
+    /// Upper bound on exclusion rounds, each with a longer query
+    pub max_queries: usize,
+    /// Result pages read for each query
+    pub pages_per_query: usize,
+    /// Pause between requests to one engine, replacing its default
+    pub delay_ms: Option<u64>,
+}
+
+impl Default for SearchEngineSettings {
+    fn default() -> Self {
+        Self {
+            max_queries: 10,
+            pages_per_query: 2,
+            delay_ms: None,
+        }
+    }
+}
+
+/// A scraped search engine and how to read its result pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchEngine {
+    Bing,
+    DuckDuckGo,
+    Yahoo,
+    Baidu,
+}
+
+impl SearchEngine {
+    fn name(&self) -> &'static str {
+        match self {
+            SearchEngine::Bing => "Bing",
+            SearchEngine::DuckDuckGo => "DuckDuckGo",
+            SearchEngine::Yahoo => "Yahoo",
+            SearchEngine::Baidu => "Baidu",
+        }
+    }
+
+    fn search_url(&self, query: &str, page: usize) -> String {
+        let query = urlencoding::encode(query);
+        match self {
+            SearchEngine::Bing => format!("https://www.bing.com/search?q={}&first={}", query, page * 10 + 1),
+            SearchEngine::DuckDuckGo => format!("https://html.duckduckgo.com/html/?q={}&s={}", query, page * 30),
+            SearchEngine::Yahoo => format!("https://search.yahoo.com/search?p={}&b={}", query, page * 10 + 1),
+            SearchEngine::Baidu => format!("https://www.baidu.com/s?wd={}&pn={}", query, page * 10),
+        }
+    }
+
+    /// Longest query the engine accepts before it silently truncates it
+    fn max_query_len(&self) -> usize {
+        match self {
+            SearchEngine::Bing => 1500,
+            SearchEngine::DuckDuckGo => 500,
+            SearchEngine::Yahoo => 1000,
+            SearchEngine::Baidu => 700,
+        }
+    }
+
+    /// Pause between two requests, short enough to finish and long
+    /// enough not to get the IP blocked
+    fn delay(&self) -> Duration {
+        match self {
+            SearchEngine::Bing | SearchEngine::Yahoo => Duration::from_millis(1500),
+            SearchEngine::DuckDuckGo | SearchEngine::Baidu => Duration::from_millis(3000),
+        }
+    }
+
+    /// Result links and the displayed URLs next to them
+    fn result_selector(&self) -> &'static str {
+        match self {
+            SearchEngine::Bing => "li.b_algo h2 a, li.b_algo cite",
+            SearchEngine::DuckDuckGo => "a.result__a, a.result__url",
+            SearchEngine::Yahoo => "div.algo a, div.algo span",
+            SearchEngine::Baidu => "div.c-container h3 a, div.c-container .c-showurl, div.c-container .c-color-gray",
+        }
+    }
+
+    /// Markers of a CAPTCHA or block page served with a 200. They are only
+    /// looked for on pages without results, so words that also turn up in
+    /// result snippets cannot mark a good page as blocked.
+    fn block_markers(&self) -> &'static [&'static str] {
+        match self {
+            SearchEngine::Bing => &["b_captcha", "/challenge/verify"],
+            SearchEngine::DuckDuckGo => &["anomaly-modal", "challenge-form"],
+            SearchEngine::Yahoo => &["action=\"/captcha", "id=\"captcha", "unusual traffic from your computer"],
+            SearchEngine::Baidu => &["wappass.baidu.com", "\u{5b89}\u{5168}\u{9a8c}\u{8bc1}"],
+        }
+    }
+
+    /// Unwraps the engine's click-tracking redirect to the real URL
+    fn unwrap_link(&self, href: &str) -> Option<String> {
+        match self {
+            SearchEngine::DuckDuckGo => {
+                let url = Url::parse(&format!("https:{}", href.trim_start_matches("https:"))).ok()?;
+                url.query_pairs()
+                    .find(|(key, _)| key == "uddg")
+                    .map(|(_, value)| value.into_owned())
+            }
+            // https://r.search.yahoo.com/.../RU=<encoded url>/RK=...
+            SearchEngine::Yahoo => {
+                let start = href.find("/RU=")? + 4;
+                let end = href[start..].find("/R").map_or(href.len(), |end| start + end);
+                urlencoding::decode(&href[start..end]).ok().map(|url| url.into_owned())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SearchEngineSource {
+    client: Arc<Client>,
+    engine: SearchEngine,
+    settings: SearchEngineSettings,
+}
+
+impl SearchEngineSource {
+    pub fn new(engine: SearchEngine) -> Self {
+        Self {
+            client: create_client(),
+            engine,
+            settings: SearchEngineSettings::default(),
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: SearchEngineSettings) {
+        self.settings = settings;
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(self.engine.search_url(&format!("site:{}", domain), 0)),
+            format: ProbeFormat::Html,
+        }
+    }
+
+    /// `site:` query that leaves out hosts already found, as many as fit
+    /// within the engine's query length
+    fn build_query(&self, domain: &str, found: &HashSet<String>) -> (String, usize) {
+        let mut query = format!("site:{}", domain);
+        let mut excluded = 0;
+
+        let mut hosts: Vec<&String> = found.iter().collect();
+        hosts.sort();
+        for host in hosts {
+            let term = format!(" -site:{}", host);
+            if query.len() + term.len() > self.engine.max_query_len() {
+                break;
+            }
+            query.push_str(&term);
+            excluded += 1;
+        }
+        (query, excluded)
+    }
+
+    async fn fetch_page(&self, query: &str, page: usize) -> Result<String, SourceError> {
+        let response = check_status(
+            self.client
+                .get(self.engine.search_url(query, page))
+                .send()
+                .await?,
+        )?;
+        let text = response.text().await?;
+
+        if !is_html_response(&text) {
+            return Err(SourceError::Parse("expected an HTML page".to_string()));
+        }
+        Ok(text)
+    }
+
+    /// Pulls hosts out of the result links and displayed URLs of a page,
+    /// failing with `RateLimited` on a block page
+    fn parse_results(&self, html: &str, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let document = Html::parse_document(html);
+        let selector = Selector::parse(self.engine.result_selector())
+            .map_err(|e| SourceError::Parse(e.to_string()))?;
+        let pattern = subdomain_pattern(domain);
+
+        let mut elements = document.select(&selector).peekable();
+        if elements.peek().is_none() {
+            let lower = html.to_lowercase();
+            if self.engine.block_markers().iter().any(|marker| lower.contains(marker)) {
+                return Err(SourceError::RateLimited);
+            }
+        }
+
+        let mut hosts = HashSet::new();
+        for element in elements {
+            let mut text = element.text().collect::<Vec<_>>().join(" ");
+            if let Some(href) = element.value().attr("href") {
+                text.push(' ');
+                text.push_str(&self.engine.unwrap_link(href).unwrap_or_else(|| href.to_string()));
+            }
+            hosts.extend(extract_subdomains(&pattern, &text, domain));
+        }
+        Ok(hosts)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+        let name = self.engine.name();
+
+        debug!("Querying {} for domain: {}", name, domain);
+
+        let delay = self
+            .settings
+            .delay_ms
+            .map_or_else(|| self.engine.delay(), Duration::from_millis);
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+        let mut last_excluded = None;
+        let mut requests = 0;
+
+        'queries: for round in 0..self.settings.max_queries.max(1) {
+            let (query, excluded) = self.build_query(domain, &subdomains);
+            // Once the query is full, further rounds would repeat it
+            if last_excluded == Some(excluded) {
+                break;
+            }
+            last_excluded = Some(excluded);
+
+            let mut new_hosts = 0;
+            for page in 0..self.settings.pages_per_query.max(1) {
+                if requests > 0 {
+                    tokio::time::sleep(delay).await;
+                }
+                requests += 1;
+
+                let parsed = match self.fetch_page(&query, page).await {
+                    Ok(html) => self.parse_results(&html, domain),
+                    Err(e) => Err(e),
+                };
+                let hosts = match parsed {
+                    Ok(hosts) => hosts,
+                    // A block would only repeat itself on the next query
+                    Err(e) => {
+                        warn!("{} stopped at query {} page {}: {}", name, round, page, e);
+                        last_error = Some(e);
+                        break 'queries;
+                    }
+                };
+
+                let before = subdomains.len();
+                subdomains.extend(hosts);
+                let added = subdomains.len() - before;
+                if added == 0 {
+                    break;
+                }
+                new_hosts += added;
+            }
+
+            results += new_hosts;
+            if new_hosts == 0 {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("{} finished: {} results in {:?}", name, results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(engine: SearchEngine) -> SearchEngineSource {
+        SearchEngineSource::new(engine)
+    }
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn extracts_names_from_result_pages() {
+        let cases = [
+            (SearchEngine::Bing, include_str!("../../tests/fixtures/searchengine/bing.html")),
+            (SearchEngine::DuckDuckGo, include_str!("../../tests/fixtures/searchengine/duckduckgo.html")),
+            (SearchEngine::Yahoo, include_str!("../../tests/fixtures/searchengine/yahoo.html")),
+            (SearchEngine::Baidu, include_str!("../../tests/fixtures/searchengine/baidu.html")),
+        ];
+        for (engine, html) in cases {
+            let hosts = source(engine).parse_results(html, "example.com").unwrap();
+            assert_eq!(
+                hosts,
+                set(&["www.example.com", "docs.example.com", "shop.example.com"]),
+                "{}",
+                engine.name()
+            );
+        }
+    }
+
+    #[test]
+    fn block_pages_are_rate_limits() {
+        let cases = [
+            (SearchEngine::Bing, include_str!("../../tests/fixtures/searchengine/bing_captcha.html")),
+            (SearchEngine::Yahoo, include_str!("../../tests/fixtures/searchengine/yahoo_captcha.html")),
+        ];
+        for (engine, html) in cases {
+            let result = source(engine).parse_results(html, "example.com");
+            assert!(matches!(result, Err(SourceError::RateLimited)), "{}", engine.name());
+        }
+    }
+
+    #[test]
+    fn captcha_in_a_result_snippet_is_not_a_block() {
+        let html = include_str!("../../tests/fixtures/searchengine/yahoo_captcha_snippet.html");
+        let hosts = source(SearchEngine::Yahoo).parse_results(html, "example.com").unwrap();
+        assert_eq!(hosts, set(&["captcha.example.com"]));
+    }
+
+    #[test]
+    fn exclusion_query_stays_within_the_engine_limit() {
+        let found: HashSet<String> = (0..200).map(|i| format!("host{:03}.example.com", i)).collect();
+        for engine in [SearchEngine::Bing, SearchEngine::DuckDuckGo, SearchEngine::Yahoo, SearchEngine::Baidu] {
+            let (query, excluded) = source(engine).build_query("example.com", &found);
+            assert!(query.len() <= engine.max_query_len(), "{}", engine.name());
+            assert!(query.starts_with("site:example.com -site:host000.example.com"));
+            assert_eq!(query.matches(" -site:").count(), excluded);
+            assert!(excluded > 0 && excluded < found.len());
+        }
+
+        let (query, excluded) = source(SearchEngine::Bing).build_query("example.com", &HashSet::new());
+        assert_eq!((query.as_str(), excluded), ("site:example.com", 0));
+    }
+}
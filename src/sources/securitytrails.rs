@@ -0,0 +1,246 @@
+use log::{debug, warn};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://api.securitytrails.com/v1";
+/// How long a key rests after a 429 while it still has quota
+const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+/// Long enough that a key with no quota left is not tried again this run
+const QUOTA_COOLDOWN: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `[securitytrails]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityTrailsSettings {
+    /// Also run the scroll-based `domains/list` search, which needs a
+    /// plan with SQL-like search access
+    pub scroll: bool,
+    /// Upper bound on scroll pages fetched
+    pub max_pages: usize,
+}
+
+impl Default for SecurityTrailsSettings {
+    fn default() -> Self {
+        Self {
+            scroll: false,
+            max_pages: 10,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SecurityTrailsSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+    settings: SecurityTrailsSettings,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubdomainsResponse {
+    subdomains: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListResponse {
+    records: Vec<ListRecord>,
+    meta: Option<ListMeta>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListRecord {
+    hostname: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListMeta {
+    scroll_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UsageResponse {
+    current_monthly_usage: u64,
+    allowed_monthly_usage: u64,
+}
+
+impl SecurityTrailsSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+            settings: SecurityTrailsSettings::default(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn set_settings(&mut self, settings: SecurityTrailsSettings) {
+        self.settings = settings;
+    }
+
+    fn subdomains_url(domain: &str) -> String {
+        format!("{}/domain/{}/subdomains?children_only=false&include_inactive=true", API_URL, domain)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(Self::subdomains_url(domain))
+                .header("APIKEY", api_key),
+            format: ProbeFormat::JsonField("subdomains"),
+        })
+    }
+
+    /// Whether the key still has monthly quota left, or `None` when the
+    /// usage endpoint cannot be read. The endpoint is free, but it is only
+    /// asked after a key has failed.
+    async fn has_quota(&self, api_key: &str) -> Option<bool> {
+        let usage: Result<UsageResponse, SourceError> = async {
+            let response = check_status(
+                self.client
+                    .get(format!("{}/account/usage", API_URL))
+                    .header("APIKEY", api_key)
+                    .send()
+                    .await?,
+            )?;
+            parse_json(&response.text().await?)
+        }
+        .await;
+
+        usage.ok().map(|usage| usage.current_monthly_usage < usage.allowed_monthly_usage)
+    }
+
+    /// Sends a request with each usable key in turn. A key that answers
+    /// 429 or 403 is checked against its monthly quota: a spent key sits
+    /// out the rest of the run, otherwise a 429 rests it briefly and a 403
+    /// drops it.
+    async fn send_rotating<T, F>(&self, keys: &mut KeyRing<'_, String>, build: F) -> Result<T, SourceError>
+    where
+        T: serde::de::DeserializeOwned,
+        F: Fn() -> RequestBuilder,
+    {
+        loop {
+            let api_key = keys.next_key().await?;
+            let result = async {
+                let response = check_status(build().header("APIKEY", api_key).send().await?)?;
+                parse_json(&response.text().await?)
+            }
+            .await;
+
+            let status = match &result {
+                Err(SourceError::RateLimited) => StatusCode::TOO_MANY_REQUESTS.as_u16(),
+                Err(SourceError::Auth(status)) => *status,
+                _ => return result,
+            };
+            if self.has_quota(api_key).await == Some(false) {
+                debug!("SecurityTrails key has no quota left this month, rotating");
+                keys.block(api_key, Instant::now() + QUOTA_COOLDOWN);
+            } else if status == StatusCode::TOO_MANY_REQUESTS.as_u16() {
+                debug!("SecurityTrails key rate limited, rotating");
+                keys.block(api_key, Instant::now() + RATE_LIMIT_COOLDOWN);
+            } else {
+                warn!("SecurityTrails rejected an API key (HTTP {}), dropping it", status);
+                keys.remove(api_key, status);
+            }
+        }
+    }
+
+    /// `subdomains` returns labels relative to the queried domain
+    async fn list_subdomains(&self, keys: &mut KeyRing<'_, String>, domain: &str, subdomains: &mut HashSet<String>) -> Result<usize, SourceError> {
+        let url = Self::subdomains_url(domain);
+        let data: SubdomainsResponse = self.send_rotating(keys, || self.client.get(&url)).await?;
+
+        let mut results = 0;
+        for label in data.subdomains {
+            let subdomain = format!("{}.{}", label.trim(), domain).to_lowercase();
+            if is_valid_subdomain(&subdomain, domain) && subdomains.insert(subdomain) {
+                results += 1;
+            }
+        }
+        Ok(results)
+    }
+
+    /// Runs the `domains/list` search and follows its scroll id
+    async fn scroll_search(&self, keys: &mut KeyRing<'_, String>, domain: &str, subdomains: &mut HashSet<String>) -> Result<usize, SourceError> {
+        let mut results = 0;
+        let body = serde_json::json!({ "query": format!("apex_domain = '{}'", domain) });
+        let list_url = format!("{}/domains/list?include_ips=false&scroll=true", API_URL);
+
+        let mut page = 0;
+        let mut scroll_id: Option<String> = None;
+        loop {
+            let data: ListResponse = match &scroll_id {
+                None => self.send_rotating(keys, || self.client.post(&list_url).json(&body)).await?,
+                Some(id) => {
+                    let url = format!("{}/scroll/{}", API_URL, id);
+                    self.send_rotating(keys, || self.client.get(&url)).await?
+                }
+            };
+
+            let records = data.records.len();
+            for record in data.records {
+                let hostname = record.hostname.to_lowercase();
+                if is_valid_subdomain(&hostname, domain) && subdomains.insert(hostname) {
+                    results += 1;
+                }
+            }
+
+            page += 1;
+            scroll_id = data.meta.and_then(|meta| meta.scroll_id);
+            if records == 0 || scroll_id.is_none() || page >= self.settings.max_pages {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+
+        debug!("Querying SecurityTrails API for domain: {}", domain);
+
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        match self.list_subdomains(&mut keys, domain, &mut subdomains).await {
+            Ok(found) => results += found,
+            Err(e) => {
+                warn!("SecurityTrails subdomains failed: {}", e);
+                last_error = Some(e);
+            }
+        }
+        if self.settings.scroll {
+            match self.scroll_search(&mut keys, domain, &mut subdomains).await {
+                Ok(found) => results += found,
+                Err(e) => {
+                    warn!("SecurityTrails domains/list failed: {}", e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("SecurityTrails finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
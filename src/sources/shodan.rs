@@ -0,0 +1,248 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://api.shodan.io";
+// Host search returns 100 matches per page
+const SEARCH_PAGE_SIZE: usize = 100;
+
+/// `[shodan]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShodanSettings {
+    /// Upper bound on pages fetched from each endpoint
+    pub max_pages: usize,
+    /// Also search hosts by `ssl.cert.subject.cn`. Every page past the
+    /// first costs a query credit.
+    pub ssl_search: bool,
+}
+
+impl Default for ShodanSettings {
+    fn default() -> Self {
+        Self {
+            max_pages: 10,
+            ssl_search: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ShodanSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+    settings: ShodanSettings,
+}
+
+#[derive(Debug, Deserialize)]
+struct DnsDomainResponse {
+    #[serde(default)]
+    subdomains: Vec<String>,
+    #[serde(default)]
+    more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct HostSearchResponse {
+    matches: Vec<HostMatch>,
+    #[serde(default)]
+    total: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct HostMatch {
+    #[serde(default)]
+    hostnames: Vec<String>,
+    ssl: Option<SslInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SslInfo {
+    cert: Option<SslCert>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SslCert {
+    subject: Option<SslSubject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SslSubject {
+    #[serde(rename = "CN")]
+    common_name: Option<String>,
+}
+
+fn normalize(name: &str, domain: &str) -> Option<String> {
+    let name = name.trim().trim_start_matches("*.").to_lowercase();
+    is_valid_subdomain(&name, domain).then_some(name)
+}
+
+/// Names on one `dns/domain` page, which lists bare labels, and whether
+/// another page follows
+fn parse_dns_domain(text: &str, domain: &str) -> Result<(HashSet<String>, bool), SourceError> {
+    let data: DnsDomainResponse = parse_json(text)?;
+    let names = data
+        .subdomains
+        .iter()
+        .filter(|label| !label.is_empty())
+        .filter_map(|label| normalize(&format!("{}.{}", label, domain), domain))
+        .collect();
+    Ok((names, data.more))
+}
+
+/// Hostnames and certificate CNs on one host search page, with the
+/// page's match count and the search total
+fn parse_host_search(text: &str, domain: &str) -> Result<(HashSet<String>, usize, usize), SourceError> {
+    let data: HostSearchResponse = parse_json(text)?;
+    let matches = data.matches.len();
+    let mut names = HashSet::new();
+    for host in data.matches {
+        let common_name = host
+            .ssl
+            .and_then(|ssl| ssl.cert)
+            .and_then(|cert| cert.subject)
+            .and_then(|subject| subject.common_name);
+        names.extend(host.hostnames.iter().chain(common_name.iter()).filter_map(|name| normalize(name, domain)));
+    }
+    Ok((names, matches, data.total))
+}
+
+impl ShodanSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+            settings: ShodanSettings::default(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub fn set_settings(&mut self, settings: ShodanSettings) {
+        self.settings = settings;
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(format!("{}/dns/domain/{}?key={}", API_URL, domain, api_key)),
+            format: ProbeFormat::JsonField("subdomains"),
+        })
+    }
+
+    /// Walks `dns/domain`, which returns bare labels
+    async fn dns_domain(&self, api_key: &str, domain: &str, subdomains: &mut HashSet<String>) -> Result<usize, SourceError> {
+        let mut results = 0;
+
+        for page in 1..=self.settings.max_pages.max(1) {
+            let url = format!("{}/dns/domain/{}?key={}&page={}", API_URL, domain, api_key, page);
+            let response = check_status(self.client.get(&url).send().await?)?;
+            let (names, more) = parse_dns_domain(&response.text().await?, domain)?;
+            results += names.into_iter().filter(|name| subdomains.insert(name.clone())).count();
+
+            if !more {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Searches hosts whose certificate subject names the domain
+    async fn ssl_search(&self, api_key: &str, domain: &str, subdomains: &mut HashSet<String>) -> Result<usize, SourceError> {
+        let mut results = 0;
+        let query = urlencoding::encode(&format!("ssl.cert.subject.cn:\"{}\"", domain)).into_owned();
+
+        for page in 1..=self.settings.max_pages.max(1) {
+            let url = format!(
+                "{}/shodan/host/search?key={}&query={}&page={}&minify=false",
+                API_URL, api_key, query, page
+            );
+            let response = check_status(self.client.get(&url).send().await?)?;
+            let (names, matches, total) = parse_host_search(&response.text().await?, domain)?;
+            results += names.into_iter().filter(|name| subdomains.insert(name.clone())).count();
+
+            if matches < SEARCH_PAGE_SIZE || page * SEARCH_PAGE_SIZE >= total {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        let api_key = self.api_keys.first().ok_or(SourceError::MissingKey)?;
+
+        debug!("Querying Shodan API for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        match self.dns_domain(api_key, domain, &mut subdomains).await {
+            Ok(found) => results += found,
+            Err(e) => {
+                warn!("Shodan dns/domain failed: {}", e);
+                last_error = Some(e);
+            }
+        }
+        if self.settings.ssl_search {
+            match self.ssl_search(api_key, domain, &mut subdomains).await {
+                Ok(found) => results += found,
+                Err(e) => {
+                    warn!("Shodan host search failed: {}", e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("Shodan finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn dns_domain_pages() {
+        let (names, more) =
+            parse_dns_domain(include_str!("../../tests/fixtures/shodan/dns_domain_page1.json"), "example.com").unwrap();
+        assert_eq!(names, set(&["www.example.com", "api.example.com", "mail.example.com"]));
+        assert!(more);
+
+        let (names, more) =
+            parse_dns_domain(include_str!("../../tests/fixtures/shodan/dns_domain_empty.json"), "example.com").unwrap();
+        assert!(names.is_empty());
+        assert!(!more);
+    }
+
+    #[test]
+    fn host_search_reads_hostnames_and_certificate_cn() {
+        let (names, matches, total) =
+            parse_host_search(include_str!("../../tests/fixtures/shodan/host_search.json"), "example.com").unwrap();
+        assert_eq!(names, set(&["vpn.example.com", "shop.example.com", "cdn.example.com"]));
+        assert_eq!(matches, 3);
+        assert_eq!(total, 3);
+    }
+}
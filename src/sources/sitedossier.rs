@@ -0,0 +1,162 @@
+use log::{debug, warn};
+use reqwest::Client;
+use scraper::{Html, Selector};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::sources::{check_status, create_client, is_html_response, is_valid_subdomain, partial_result, Probe, ProbeFormat, SourceError};
+
+const PAGE_SIZE: usize = 100;
+const MAX_PAGES: usize = 50;
+// SiteDossier shows a CAPTCHA quickly when pages are fetched back to back
+const PAGE_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct SiteDossierSource {
+    client: Arc<Client>,
+}
+
+/// Hosts on one listing page and whether a further page exists
+struct ListingPage {
+    hosts: Vec<String>,
+    has_next: bool,
+}
+
+impl SiteDossierSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+        }
+    }
+
+    // Listings are numbered by their first item: 1, 101, 201, ...
+    fn page_url(domain: &str, page: usize) -> String {
+        format!("http://www.sitedossier.com/parentdomain/{}/{}", domain, page * PAGE_SIZE + 1)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(Self::page_url(domain, 0)),
+            format: ProbeFormat::Html,
+        }
+    }
+
+    // Blocked clients get a CAPTCHA page instead of the listing. Listings
+    // can name hosts like captcha.example.com, so this is only asked of
+    // pages without any
+    fn is_captcha(html: &str) -> bool {
+        let lower = html.to_lowercase();
+        lower.contains("captcha") || lower.contains("please enter the letters")
+    }
+
+    fn parse_page(html: &str, domain: &str, page: usize) -> Result<ListingPage, SourceError> {
+        let document = Html::parse_document(html);
+        let site_selector = Selector::parse("a[href^='/site/']")
+            .map_err(|e| SourceError::Parse(e.to_string()))?;
+        let next_selector = Selector::parse("a[href^='/parentdomain/']")
+            .map_err(|e| SourceError::Parse(e.to_string()))?;
+
+        let hosts: Vec<String> = document
+            .select(&site_selector)
+            .filter_map(|el| el.value().attr("href"))
+            .map(|href| href.trim_start_matches("/site/").trim_end_matches('/').to_lowercase())
+            .collect();
+        if hosts.is_empty() && Self::is_captcha(html) {
+            return Err(SourceError::RateLimited);
+        }
+
+        let next_start = format!("/{}", (page + 1) * PAGE_SIZE + 1);
+        let has_next = document
+            .select(&next_selector)
+            .filter_map(|el| el.value().attr("href"))
+            .any(|href| href.starts_with(&format!("/parentdomain/{}", domain)) && href.ends_with(&next_start));
+
+        Ok(ListingPage { hosts, has_next })
+    }
+
+    async fn fetch_page(&self, domain: &str, page: usize) -> Result<String, SourceError> {
+        let response = check_status(self.client.get(Self::page_url(domain, page)).send().await?)?;
+        let text = response.text().await?;
+
+        // SiteDossier is scraped, so anything but an HTML page means the site changed
+        if !is_html_response(&text) {
+            return Err(SourceError::Parse("expected an HTML page".to_string()));
+        }
+        Ok(text)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        debug!("Querying SiteDossier for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        for page in 0..MAX_PAGES {
+            if page > 0 {
+                tokio::time::sleep(PAGE_DELAY).await;
+            }
+
+            let listing = match self.fetch_page(domain, page).await {
+                Ok(html) => Self::parse_page(&html, domain, page),
+                Err(e) => Err(e),
+            };
+            let listing = match listing {
+                Ok(listing) => listing,
+                Err(e) => {
+                    warn!("SiteDossier stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            for host in listing.hosts {
+                if is_valid_subdomain(&host, domain) && subdomains.insert(host) {
+                    results += 1;
+                }
+            }
+
+            if !listing.has_next {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("SiteDossier finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_ONE: &str = include_str!("../../tests/fixtures/sitedossier/page1.html");
+    const PAGE_TWO: &str = include_str!("../../tests/fixtures/sitedossier/page2.html");
+
+    #[test]
+    fn listing_pages_give_hosts_and_the_next_link() {
+        let first = SiteDossierSource::parse_page(PAGE_ONE, "example.com", 0).unwrap();
+        assert_eq!(first.hosts, vec!["www.example.com", "mail.example.com", "example.com"]);
+        assert!(first.has_next);
+
+        let second = SiteDossierSource::parse_page(PAGE_TWO, "example.com", 1).unwrap();
+        assert_eq!(second.hosts, vec!["vpn.example.com"]);
+        assert!(!second.has_next);
+    }
+
+    #[test]
+    fn captcha_page_is_detected() {
+        let captcha = SiteDossierSource::parse_page(include_str!("../../tests/fixtures/sitedossier/captcha.html"), "example.com", 0);
+        assert!(matches!(captcha, Err(SourceError::RateLimited)));
+    }
+
+    #[test]
+    fn captcha_host_in_a_listing_is_not_a_block() {
+        let listing = SiteDossierSource::parse_page(include_str!("../../tests/fixtures/sitedossier/captcha_host.html"), "example.com", 0).unwrap();
+        assert_eq!(listing.hosts, vec!["captcha.example.com", "shop.example.com"]);
+    }
+}
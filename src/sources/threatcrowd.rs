@@ -1,4 +1,3 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
@@ -7,7 +6,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, Probe, ProbeFormat, SourceError};
 
 const MAX_RETRIES: u32 = 3;
 const RETRY_DELAY: Duration = Duration::from_secs(2);
@@ -31,7 +30,17 @@ impl ThreatCrowdSource {
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(format!(
+                "https://www.threatcrowd.org/searchApi/v2/domain/report/?domain={}",
+                domain
+            )),
+            format: ProbeFormat::JsonField("response_code"),
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
         let mut errors = 0;
@@ -80,31 +89,19 @@ impl ThreatCrowdSource {
             }
         }
 
-        warn!("ThreatCrowd failed after {} retries: {}", MAX_RETRIES, last_error.unwrap_or_else(|| anyhow::anyhow!("Unknown error")));
-        Ok(HashSet::new())
+        let e = last_error.unwrap_or_else(|| SourceError::Network("no request made".to_string()));
+        warn!("ThreatCrowd failed after {} retries: {}", MAX_RETRIES, e);
+        Err(e)
     }
 
-    async fn try_request(&self, url: &str) -> Result<Response> {
-        let response = self.client
-            .get(url)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-            .send()
-            .await
-            .context("Failed to query ThreatCrowd")?;
-
-        let status = response.status();
-        if status.is_server_error() {
-            return Err(anyhow::anyhow!("ThreatCrowd server error: {}", status));
-        }
-
-        if !status.is_success() {
-            return Err(anyhow::anyhow!("ThreatCrowd returned error status: {}", status));
-        }
-
-        let text = response.text().await
-            .context("Failed to read ThreatCrowd response")?;
-
-        serde_json::from_str(&text)
-            .context("Failed to parse ThreatCrowd response")
+    async fn try_request(&self, url: &str) -> Result<Response, SourceError> {
+        let response = check_status(
+            self.client
+                .get(url)
+                .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
+                .send()
+                .await?,
+        )?;
+        parse_json(&response.text().await?)
     }
 }
@@ -0,0 +1,107 @@
+use log::debug;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, Probe, ProbeFormat, SourceError};
+
+#[derive(Clone)]
+pub struct ThreatMinerSource {
+    client: Arc<Client>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThreatMinerResponse {
+    status_code: String,
+    #[serde(default)]
+    results: Vec<String>,
+}
+
+fn parse_response(text: &str, domain: &str) -> Result<HashSet<String>, SourceError> {
+    let data: ThreatMinerResponse = parse_json(text)?;
+
+    // The status lives in the body; "404" just means nothing is known
+    match data.status_code.as_str() {
+        "200" | "404" => {}
+        status => {
+            return Err(match status.parse().ok().and_then(|code| StatusCode::from_u16(code).ok()) {
+                Some(code) => SourceError::from_status(code),
+                None => SourceError::Parse(format!("unexpected status_code '{}'", status)),
+            });
+        }
+    }
+
+    Ok(data
+        .results
+        .iter()
+        .map(|subdomain| subdomain.trim().to_lowercase())
+        .filter(|subdomain| is_valid_subdomain(subdomain, domain))
+        .collect())
+}
+
+impl ThreatMinerSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+        }
+    }
+
+    // rt=5 is the subdomain report
+    fn url(domain: &str) -> String {
+        format!("https://api.threatminer.org/v2/domain.php?q={}&rt=5", domain)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.client.get(Self::url(domain)),
+            format: ProbeFormat::JsonField("results"),
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        debug!("Querying ThreatMiner for domain: {}", domain);
+
+        let response = check_status(self.client.get(Self::url(domain)).send().await?)?;
+        let subdomains = parse_response(&response.text().await?, domain)?;
+        results += subdomains.len();
+
+        let elapsed = start_time.elapsed();
+        debug!("ThreatMiner finished: {} results in {:?}", results, elapsed);
+        Ok(subdomains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_results_and_body_status() {
+        let names = parse_response(include_str!("../../tests/fixtures/threatminer/found.json"), "example.com").unwrap();
+        let expected: HashSet<String> = ["www.example.com", "mail.example.com"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(names, expected);
+
+        let names = parse_response(include_str!("../../tests/fixtures/threatminer/not_found.json"), "example.com").unwrap();
+        assert!(names.is_empty());
+    }
+
+    #[test]
+    fn unknown_status_codes_are_errors() {
+        let throttled = r#"{"status_code": "429", "status_message": "Rate limit", "results": []}"#;
+        assert!(matches!(parse_response(throttled, "example.com"), Err(SourceError::RateLimited)));
+
+        let rejected = r#"{"status_code": "403", "status_message": "Forbidden", "results": []}"#;
+        assert!(matches!(parse_response(rejected, "example.com"), Err(SourceError::Auth(403))));
+
+        let broken = r#"{"status_code": "500", "status_message": "Server error", "results": []}"#;
+        assert!(matches!(parse_response(broken, "example.com"), Err(SourceError::HttpStatus(500))));
+
+        let garbled = r#"{"status_code": "OK", "status_message": "Results found.", "results": []}"#;
+        assert!(matches!(parse_response(garbled, "example.com"), Err(SourceError::Parse(_))));
+    }
+}
@@ -0,0 +1,142 @@
+use log::{debug, warn};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, Probe, ProbeFormat, SourceError};
+
+const SEARCH_URL: &str = "https://urlscan.io/api/v1/search/";
+const PAGE_SIZE: usize = 100;
+// Unauthenticated searches are throttled hard; this keeps a run within
+// the hourly allowance
+const MAX_PAGES: usize = 20;
+
+#[derive(Clone)]
+pub struct URLScanSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    results: Vec<SearchResult>,
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResult {
+    task: Option<ScanTarget>,
+    page: Option<ScanTarget>,
+    #[serde(default)]
+    sort: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScanTarget {
+    domain: Option<String>,
+}
+
+// The sort values of the last hit, joined the way `search_after` expects
+fn search_after(sort: &[Value]) -> Option<String> {
+    let parts: Vec<String> = sort
+        .iter()
+        .map(|value| match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(","))
+}
+
+impl URLScanSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    // The search works without a key; a key only raises the rate limits
+    fn get(&self, url: &str) -> RequestBuilder {
+        let request = self.client.get(url);
+        match self.api_keys.first() {
+            Some(api_key) => request.header("API-Key", api_key),
+            None => request,
+        }
+    }
+
+    fn search_url(domain: &str, size: usize, after: Option<&str>) -> String {
+        let mut url = format!("{}?q=domain:{}&size={}", SEARCH_URL, domain, size);
+        if let Some(after) = after {
+            url.push_str(&format!("&search_after={}", urlencoding::encode(after)));
+        }
+        url
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self.get(&Self::search_url(domain, 1, None)),
+            format: ProbeFormat::JsonField("results"),
+        }
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        debug!("Querying URLScan for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+        let mut after: Option<String> = None;
+
+        for page in 0..MAX_PAGES {
+            let url = Self::search_url(domain, PAGE_SIZE, after.as_deref());
+            let data: SearchResponse = match async {
+                let response = check_status(self.get(&url).send().await?)?;
+                parse_json(&response.text().await?)
+            }
+            .await
+            {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("URLScan stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            after = data.results.last().and_then(|result| search_after(&result.sort));
+            for result in data.results {
+                // The scanned URL and the page it ended up on after redirects
+                for target in [result.task, result.page].into_iter().flatten() {
+                    let Some(host) = target.domain else { continue };
+                    let host = host.to_lowercase();
+                    if is_valid_subdomain(&host, domain) && subdomains.insert(host) {
+                        results += 1;
+                    }
+                }
+            }
+
+            if !data.has_more || after.is_none() {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("URLScan finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
@@ -1,4 +1,3 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use rand::seq::SliceRandom;
 use reqwest::Client;
@@ -7,7 +6,7 @@ use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use crate::sources::create_client;
+use crate::sources::{check_status, parse_json, partial_result, Probe, ProbeFormat, SourceError};
 
 #[derive(Clone)]
 pub struct VirusTotalSource {
@@ -46,24 +45,47 @@ impl VirusTotalSource {
         self.api_keys.extend(keys);
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(format!(
+                    "https://www.virustotal.com/api/v3/domains/{}/subdomains?limit=1",
+                    domain
+                ))
+                .header("x-apikey", api_key),
+            format: ProbeFormat::JsonField("data"),
+        })
+    }
+
+    async fn fetch_page(&self, url: &str, api_key: &str) -> Result<Response, SourceError> {
+        let response = check_status(
+            self.client
+                .get(url)
+                .header("x-apikey", api_key)
+                .send()
+                .await?,
+        )?;
+        parse_json(&response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         debug!("Querying VirusTotal for domain: {}", domain);
 
-        // Check if API keys are available
-        if self.api_keys.is_empty() {
-            warn!("No VirusTotal API keys provided, skipping...");
-            return Ok(HashSet::new());
-        }
-
         // Randomly select an API key
         let api_key = self.api_keys.choose(&mut rand::thread_rng())
-            .context("Failed to select API key")?;
+            .ok_or(SourceError::MissingKey)?;
 
         let mut subdomains = HashSet::new();
+        let mut last_error = None;
         let mut cursor = None;
 
         loop {
@@ -76,32 +98,11 @@ impl VirusTotalSource {
                 url.push_str(&format!("&cursor={}", cur));
             }
 
-            let response = match self.client
-                .get(&url)
-                .header("x-apikey", api_key)
-                .send()
-                .await
-            {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        errors += 1;
-                        warn!("VirusTotal API returned error status: {}", resp.status());
-                        break;
-                    }
-                    resp
-                }
-                Err(e) => {
-                    errors += 1;
-                    warn!("Failed to query VirusTotal: {}", e);
-                    break;
-                }
-            };
-
-            let data: Response = match response.json().await {
-                Ok(d) => d,
+            let data = match self.fetch_page(&url, api_key).await {
+                Ok(data) => data,
                 Err(e) => {
-                    errors += 1;
-                    warn!("Failed to parse VirusTotal response: {}", e);
+                    warn!("VirusTotal pagination stopped early: {}", e);
+                    last_error = Some(e);
                     break;
                 }
             };
@@ -125,10 +126,7 @@ impl VirusTotalSource {
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "VirusTotal finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("VirusTotal finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
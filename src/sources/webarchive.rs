@@ -1,32 +1,52 @@
-use anyhow::{Context, Result};
 use log::{debug, warn};
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::HashSet;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
 use url::Url;
 
-use crate::sources::{create_client, is_valid_subdomain};
+use crate::sources::{check_status, for_each_line, is_valid_subdomain, partial_result, Probe, ProbeFormat, SourceError};
 
-#[derive(Clone)]
-pub struct WebArchiveSource {
-    client: Arc<Client>,
+const CDX_URL: &str = "https://web.archive.org/cdx/search/cdx";
+
+/// `[webarchive]` section of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebArchiveSettings {
+    /// Captures requested per CDX page
+    pub page_size: usize,
+    /// Upper bound on CDX pages fetched
+    pub max_pages: usize,
+    /// File that receives every distinct archived URL, for later crawling
+    pub urls_output: Option<PathBuf>,
 }
 
-#[derive(Debug, Deserialize)]
-struct WaybackResponse {
-    archived_snapshots: ArchivedSnapshots,
+impl Default for WebArchiveSettings {
+    fn default() -> Self {
+        Self {
+            page_size: 10000,
+            max_pages: 20,
+            urls_output: None,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct ArchivedSnapshots {
-    closest: Option<Snapshot>,
+#[derive(Clone)]
+pub struct WebArchiveSource {
+    client: Arc<Client>,
+    settings: WebArchiveSettings,
 }
 
-#[derive(Debug, Deserialize)]
-struct Snapshot {
-    url: String,
+// CDX "original" values are usually full URLs but some lack a scheme
+fn url_host(original: &str) -> Option<String> {
+    let url = Url::parse(original)
+        .or_else(|_| Url::parse(&format!("http://{}", original)))
+        .ok()?;
+    url.host_str().map(|host| host.to_lowercase())
 }
 
 impl WebArchiveSource {
@@ -36,76 +56,128 @@ impl WebArchiveSource {
                 .user_agent("subfinder-rs")
                 .build()
                 .expect("Failed to build HTTP client")),
+            settings: WebArchiveSettings::default(),
         }
     }
 
-    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>> {
+    pub fn set_settings(&mut self, settings: WebArchiveSettings) {
+        self.settings = settings;
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Probe {
+        Probe {
+            request: self
+                .client
+                .get(format!("{}?url=*.{}&fl=original&collapse=urlkey&limit=1", CDX_URL, domain)),
+            format: ProbeFormat::Text,
+        }
+    }
+
+    fn page_url(&self, domain: &str, resume_key: Option<&str>) -> String {
+        let mut url = format!(
+            "{}?url=*.{}&fl=original&collapse=urlkey&limit={}&showResumeKey=true",
+            CDX_URL, domain, self.settings.page_size
+        );
+        if let Some(key) = resume_key {
+            url.push_str(&format!("&resumeKey={}", urlencoding::encode(key)));
+        }
+        url
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
         let start_time = Instant::now();
         let mut results = 0;
-        let mut errors = 0;
 
         debug!("Querying web archive for domain: {}", domain);
-        
-        let url = format!(
-            "https://archive.org/wayback/available?url={}&timestamp=*",
-            domain
-        );
 
-        // Use optimized connection settings
-        let response = match self.client
-            .get(&url)
-            .header("Connection", "keep-alive")
-            .header("Keep-Alive", "timeout=60")
-            .header("Accept", "application/json")
-            .header("Accept-Language", "en-US,en;q=0.9")
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                if !resp.status().is_success() {
-                    errors += 1;
-                    warn!("Web Archive returned error status: {}", resp.status());
-                    return Ok(HashSet::new());
+        let mut urls_output = match &self.settings.urls_output {
+            Some(path) => match File::create(path).await {
+                Ok(file) => Some(BufWriter::new(file)),
+                Err(e) => {
+                    warn!("Failed to create archived URL file {}: {}", path.display(), e);
+                    None
                 }
-                resp
-            }
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to query web archive: {}", e);
-                return Ok(HashSet::new());
-            }
-        };
-
-        let wayback_data: WaybackResponse = match response
-            .json()
-            .await
-        {
-            Ok(data) => data,
-            Err(e) => {
-                errors += 1;
-                warn!("Failed to parse web archive response: {}", e);
-                return Ok(HashSet::new());
-            }
+            },
+            None => None,
         };
 
         let mut subdomains = HashSet::new();
-        if let Some(snapshot) = wayback_data.archived_snapshots.closest {
-            if let Ok(url) = Url::parse(&snapshot.url) {
-                if let Some(host) = url.host_str() {
-                    let host = host.to_lowercase();
-                    if is_valid_subdomain(&host, domain) {
-                        results += 1;
-                        subdomains.insert(host);
+        let mut last_error = None;
+        let mut resume_key: Option<String> = None;
+        let mut page = 0;
+
+        loop {
+            let url = self.page_url(domain, resume_key.as_deref());
+            let response = match self.client.get(&url).send().await {
+                Ok(response) => check_status(response),
+                Err(e) => Err(SourceError::from(e)),
+            };
+
+            // With showResumeKey the captures are followed by a blank line
+            // and the key for the next page
+            let mut after_blank = false;
+            let mut next_key = None;
+            let mut archived = Vec::new();
+            let streamed = match response {
+                Ok(response) => {
+                    for_each_line(response, |line| {
+                        if line.is_empty() {
+                            after_blank = true;
+                            return;
+                        }
+                        if after_blank {
+                            next_key = Some(line.to_string());
+                            return;
+                        }
+
+                        if urls_output.is_some() {
+                            archived.push(line.to_string());
+                        }
+                        if let Some(host) = url_host(line) {
+                            if is_valid_subdomain(&host, domain) && subdomains.insert(host) {
+                                results += 1;
+                            }
+                        }
+                    })
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+
+            // Written once the page is in, since the line callback cannot await
+            if let Some(writer) = urls_output.as_mut() {
+                for line in archived {
+                    if let Err(e) = writer.write_all(format!("{}\n", line).as_bytes()).await {
+                        warn!("Failed to write archived URL: {}", e);
+                        break;
                     }
                 }
             }
+
+            match streamed {
+                Ok(()) => {}
+                Err(e) => {
+                    warn!("Web Archive stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            }
+
+            page += 1;
+            resume_key = next_key;
+            if resume_key.is_none() || page >= self.settings.max_pages {
+                break;
+            }
+        }
+
+        if let Some(mut writer) = urls_output {
+            if let Err(e) = writer.flush().await {
+                warn!("Failed to write archived URLs: {}", e);
+            }
         }
 
         let elapsed = start_time.elapsed();
-        debug!(
-            "Web Archive finished: {} results, {} errors in {:?}",
-            results, errors, elapsed
-        );
-        Ok(subdomains)
+        debug!("Web Archive finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
     }
 }
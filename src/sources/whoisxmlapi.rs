@@ -0,0 +1,92 @@
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://subdomains.whoisxmlapi.com/api/v1";
+
+#[derive(Clone)]
+pub struct WhoisXMLAPISource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhoisXMLResponse {
+    result: WhoisXMLResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhoisXMLResult {
+    #[serde(default)]
+    records: Vec<WhoisXMLRecord>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WhoisXMLRecord {
+    domain: String,
+}
+
+impl WhoisXMLAPISource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    fn url(api_key: &str, domain: &str) -> String {
+        format!("{}?apiKey={}&domainName={}", API_URL, api_key, domain)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self.client.get(Self::url(api_key, domain)),
+            format: ProbeFormat::JsonField("result"),
+        })
+    }
+
+    async fn fetch(&self, api_key: &str, domain: &str) -> Result<WhoisXMLResponse, SourceError> {
+        let response = check_status(self.client.get(Self::url(api_key, domain)).send().await?)?;
+        parse_json(&response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying WhoisXML API for domain: {}", domain);
+
+        let data = keys.with_key(|api_key| self.fetch(api_key, domain)).await?;
+
+        let mut subdomains = HashSet::new();
+        for record in data.result.records {
+            let name = record.domain.to_lowercase();
+            if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                results += 1;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("WhoisXML API finished: {} results in {:?}", results, elapsed);
+        Ok(subdomains)
+    }
+}
@@ -0,0 +1,117 @@
+use log::{debug, warn};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::sources::{check_status, create_client, is_valid_subdomain, parse_json, partial_result, KeyRing, Probe, ProbeFormat, SourceError};
+
+const API_URL: &str = "https://api.zoomeye.hk/domain/search";
+const PAGE_SIZE: usize = 1000;
+const MAX_PAGES: usize = 10;
+
+#[derive(Clone)]
+pub struct ZoomEyeSource {
+    client: Arc<Client>,
+    api_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZoomEyeResponse {
+    #[serde(default)]
+    total: usize,
+    #[serde(default)]
+    list: Vec<ZoomEyeDomain>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZoomEyeDomain {
+    name: String,
+}
+
+impl ZoomEyeSource {
+    pub fn new() -> Self {
+        Self {
+            client: create_client(),
+            api_keys: Vec::new(),
+        }
+    }
+
+    pub fn add_api_keys(&mut self, keys: Vec<String>) {
+        self.api_keys.extend(keys);
+    }
+
+    pub fn has_api_keys(&self) -> bool {
+        !self.api_keys.is_empty()
+    }
+
+    // type=1 asks for subdomains rather than associated domains
+    fn page_url(domain: &str, size: usize, page: usize) -> String {
+        format!("{}?q={}&type=1&s={}&page={}", API_URL, domain, size, page)
+    }
+
+    pub(crate) fn probe(&self, domain: &str) -> Option<Probe> {
+        let api_key = self.api_keys.first()?;
+        Some(Probe {
+            request: self
+                .client
+                .get(Self::page_url(domain, 1, 1))
+                .header("API-KEY", api_key),
+            format: ProbeFormat::JsonField("list"),
+        })
+    }
+
+    async fn fetch_page(&self, api_key: &str, domain: &str, page: usize) -> Result<ZoomEyeResponse, SourceError> {
+        let response = check_status(
+            self.client
+                .get(Self::page_url(domain, PAGE_SIZE, page))
+                .header("API-KEY", api_key)
+                .send()
+                .await?,
+        )?;
+        parse_json(&response.text().await?)
+    }
+
+    pub async fn enumerate(&self, domain: &str) -> Result<HashSet<String>, SourceError> {
+        let start_time = Instant::now();
+        let mut results = 0;
+
+        if self.api_keys.is_empty() {
+            return Err(SourceError::MissingKey);
+        }
+        let mut keys = KeyRing::new(&self.api_keys);
+
+        debug!("Querying ZoomEye API for domain: {}", domain);
+
+        let mut subdomains = HashSet::new();
+        let mut last_error = None;
+
+        for page in 1..=MAX_PAGES {
+            let data = match keys.with_key(|api_key| self.fetch_page(api_key, domain, page)).await {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("ZoomEye stopped at page {}: {}", page, e);
+                    last_error = Some(e);
+                    break;
+                }
+            };
+
+            let entries = data.list.len();
+            for entry in data.list {
+                let name = entry.name.to_lowercase();
+                if is_valid_subdomain(&name, domain) && subdomains.insert(name) {
+                    results += 1;
+                }
+            }
+
+            if entries < PAGE_SIZE || page * PAGE_SIZE >= data.total {
+                break;
+            }
+        }
+
+        let elapsed = start_time.elapsed();
+        debug!("ZoomEye finished: {} results in {:?}", results, elapsed);
+        partial_result(subdomains, last_error)
+    }
+}
@@ -0,0 +1,25 @@
+-- Stand-in for the parts of crt.sh's certwatch database that the SQL
+-- backend reads. CERTIFICATE holds the notAfter timestamp as text instead
+-- of DER, and x509_notAfter reads it back, so no certificates are needed.
+DROP SCHEMA IF EXISTS crtsh_fixture CASCADE;
+CREATE SCHEMA crtsh_fixture;
+SET search_path TO crtsh_fixture;
+
+CREATE TABLE certificate_and_identities (
+    CERTIFICATE bytea NOT NULL,
+    NAME_TYPE text NOT NULL,
+    NAME_VALUE text NOT NULL
+);
+
+CREATE FUNCTION x509_notAfter(bytea) RETURNS timestamp
+    AS $$ SELECT convert_from($1, 'UTF8')::timestamp $$
+    LANGUAGE sql IMMUTABLE;
+
+INSERT INTO certificate_and_identities (CERTIFICATE, NAME_TYPE, NAME_VALUE) VALUES
+    (convert_to('2999-01-01 00:00:00', 'UTF8'), 'san:dNSName', 'www.example.com'),
+    (convert_to('2999-01-01 00:00:00', 'UTF8'), 'san:dNSName', '*.API.example.com'),
+    (convert_to('2999-01-01 00:00:00', 'UTF8'), '2.5.4.3', 'mail.example.com'),
+    (convert_to('2000-01-01 00:00:00', 'UTF8'), 'san:dNSName', 'old.example.com'),
+    (convert_to('2999-01-01 00:00:00', 'UTF8'), 'san:dNSName', 'example.com'),
+    (convert_to('2999-01-01 00:00:00', 'UTF8'), 'san:dNSName', 'www.notexample.com'),
+    (convert_to('2999-01-01 00:00:00', 'UTF8'), 'san:dNSName', 'www.example.com.evil.org');
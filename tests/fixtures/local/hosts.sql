@@ -0,0 +1,6 @@
+CREATE TABLE hosts (id INTEGER PRIMARY KEY, name TEXT, owner TEXT);
+INSERT INTO hosts (name, owner) VALUES
+    ('vpn.example.com', 'net'),
+    ('Intranet.Example.com.', 'it'),
+    (NULL, 'nobody'),
+    ('example.community', 'marketing');